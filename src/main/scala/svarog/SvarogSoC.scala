@@ -22,6 +22,7 @@ class SvarogSoC(
   private val xlen = config.getMaxWordLen
   private val startAddress = bootloader
     .map(_ => 0x00480000L)
+    .orElse(config.resetVector)
     .getOrElse(config.memories.head.getBaseAddress)
 
   private val xbar = LazyModule(new TLXbar)
@@ -117,6 +118,12 @@ class SvarogSoC(
     val allDebugPorts = tiles.flatMap(_.module.io.debug)
     val allRegData = tiles.flatMap(_.module.io.debugRegData)
     val allHalted = tiles.flatMap(_.module.io.halt)
+    val allRetired = tiles.flatMap(_.module.io.debugRetired)
+
+    // External interrupts only have a source when the debug module drives them
+    val externalInterrupts = WireDefault(
+      VecInit(Seq.fill(config.getNumHarts)(false.B))
+    )
 
     outer.debugModule match {
       case Some(debugLazy) =>
@@ -130,12 +137,9 @@ class SvarogSoC(
           port <> dbg.harts(i)
         }
 
-        // Connect register data (use first hart's data for now)
-        dbg.cpuRegData := allRegData.headOption.getOrElse {
-          val w = Wire(Valid(UInt(xlen.W)))
-          w.valid := false.B
-          w.bits := 0.U
-          w
+        // Connect register data
+        allRegData.zipWithIndex.foreach { case (regData, i) =>
+          dbg.cpuRegData(i) := regData
         }
 
         // Connect halt status
@@ -143,6 +147,14 @@ class SvarogSoC(
           dbg.cpuHalted(i) := halt
         }
 
+        // Connect retired PC
+        allRetired.zipWithIndex.foreach { case (retired, i) =>
+          dbg.cpuRetired(i) := retired
+        }
+
+        // Connect external interrupt lines
+        externalInterrupts := dbg.externalInterrupt
+
       case None =>
         // No debug module - tie off debug ports
         allDebugPorts.foreach { d =>
@@ -171,6 +183,7 @@ class SvarogSoC(
         tile.module.io.timerInterrupt(i) := outer.timer.module.io.fire(hartIdx)
         tile.module.io.softwareInterrupt(i) := outer.msip.module.io
           .fire(hartIdx)
+        tile.module.io.externalInterrupt(i) := externalInterrupts(hartIdx)
         hartIdx += 1
       }
     }
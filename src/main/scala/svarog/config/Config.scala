@@ -113,7 +113,8 @@ case class TCM(baseAddress: Long, length: Long) extends Memory {
 case class SoCYaml(
     clusters: Seq[Cluster],
     io: Seq[IO],
-    memories: Seq[Memory]
+    memories: Seq[Memory],
+    resetVector: Option[Long] = None
 )
 
 /** Complete SoC configuration (YAML + runtime flags) */
@@ -121,7 +122,8 @@ case class SoC(
     clusters: Seq[Cluster],
     io: Seq[IO],
     memories: Seq[Memory],
-    simulatorDebug: Boolean
+    simulatorDebug: Boolean,
+    resetVector: Option[Long] = None
 ) {
   def getMaxWordLen: Int = clusters.map(_.isa.xlen).maxOption.getOrElse(0)
   def getNumHarts: Int = clusters.map(_.numCores).sum
@@ -135,7 +137,8 @@ object SoC {
       clusters = yaml.clusters,
       io = yaml.io,
       memories = yaml.memories,
-      simulatorDebug = simulatorDebug
+      simulatorDebug = simulatorDebug,
+      resetVector = yaml.resetVector
     )
   }
 }
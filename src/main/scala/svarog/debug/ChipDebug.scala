@@ -33,6 +33,8 @@ class ChipDebugSimulatorIO(numHarts: Int, xlen: Int) extends Bundle {
   val mem_res = Decoupled(UInt(xlen.W))
   val reg_res = Decoupled(UInt(xlen.W))
   val halted = Output(Bool())
+  val retired = Output(Valid(UInt(xlen.W))) // PC of the retiring instruction
+  val irq = Input(UInt(numHarts.W)) // Machine external interrupt of each hart
 }
 
 class TLChipDebugModule(
@@ -70,8 +72,10 @@ class TLChipDebugModule(
   class Impl extends LazyModuleImp(this) {
     val debug = IO(new ChipDebugSimulatorIO(numHarts, xlen))
     val harts = IO(Vec(numHarts, new HartDebugIO(xlen)))
-    val cpuRegData = IO(Input(Valid(UInt(xlen.W))))
+    val cpuRegData = IO(Input(Vec(numHarts, Valid(UInt(xlen.W)))))
     val cpuHalted = IO(Input(Vec(numHarts, Bool())))
+    val cpuRetired = IO(Input(Vec(numHarts, Valid(UInt(xlen.W)))))
+    val externalInterrupt = IO(Output(Vec(numHarts, Bool())))
 
     private val (instOut, instEdge) = instNode.out(0)
     private val (dataOut, dataEdge) = dataNode.out(0)
@@ -146,12 +150,24 @@ class TLChipDebugModule(
       )
     }
 
+    // Status outputs follow the last addressed hart
+    val selectedHart =
+      RegEnable(debug.hart_in.id.bits, 0.U(8.W), debug.hart_in.id.valid)
+    val statusSel = (0 until numHarts).map(i => selectedHart === i.U)
+
     // Pass through halt status
-    debug.halted := cpuHalted(0)
+    debug.halted := Mux1H(statusSel, cpuHalted)
+
+    // Pass through retired PC
+    debug.retired := Mux1H(statusSel, cpuRetired)
+
+    // Interrupt lines are level sensitive and not tied to the addressed hart
+    externalInterrupt := debug.irq.asBools
 
-    // Connect register results from CPU
-    debug.reg_res.valid := cpuRegData.valid
-    debug.reg_res.bits := cpuRegData.bits
+    // Connect register results from CPU. A hart keeps its last result valid
+    // until its next request, so pick the addressed hart rather than any valid
+    debug.reg_res.valid := Mux1H(statusSel, cpuRegData.map(_.valid))
+    debug.reg_res.bits := Mux1H(statusSel, cpuRegData.map(_.bits))
 
     // Memory interface state machine
     val wordSize = xlen / 8
@@ -36,10 +36,12 @@ class CpuIO(xlen: Int) extends Bundle {
   // Debug and control
   val debug = Flipped(new HartDebugIO(xlen))
   val debugRegData = Valid(UInt(xlen.W))
+  val debugRetired = Valid(UInt(xlen.W))
   val halt = Output(Bool())
   // Interrupt inputs
   val timerInterrupt = Input(Bool())
   val softwareInterrupt = Input(Bool())
+  val externalInterrupt = Input(Bool())
 }
 
 class Cpu(
@@ -179,7 +181,7 @@ class CpuImp(outer: Cpu) extends LazyModuleImp(outer) {
   // Connect interrupt signals to InterruptCSR
   outer.interruptCSR.module.io.timerInterrupt := io.timerInterrupt
   outer.interruptCSR.module.io.softwareInterrupt := io.softwareInterrupt
-  outer.interruptCSR.module.io.externalInterrupt := false.B
+  outer.interruptCSR.module.io.externalInterrupt := io.externalInterrupt
 
   // Core Local Interrupter - interrupt arbitration logic
   val clint = Module(new CoreLocalInterrupter(xlen))
@@ -289,6 +291,7 @@ class CpuImp(outer: Cpu) extends LazyModuleImp(outer) {
 
   // Debug connections
   debug.io.wbPC <> writeback.io.debugPC
+  io.debugRetired := writeback.io.debugPC
   debug.io.memStore <> writeback.io.debugStore
   fetch.io.debugSetPC <> debug.io.setPCOut
   fetch.io.halt := halt
@@ -71,9 +71,11 @@ class MicroTileImp(outer: MicroTile) extends LazyModuleImp(outer) {
   val io = IO(new Bundle {
     val debug = Vec(numCores, Flipped(new HartDebugIO(xlen)))
     val debugRegData = Vec(numCores, Valid(UInt(xlen.W)))
+    val debugRetired = Vec(numCores, Valid(UInt(xlen.W)))
     val halt = Output(Vec(numCores, Bool()))
     val timerInterrupt = Input(Vec(numCores, Bool()))
     val softwareInterrupt = Input(Vec(numCores, Bool()))
+    val externalInterrupt = Input(Vec(numCores, Bool()))
   })
 
   require(outer.instNodes.length == numCores, "instNodes must match numCores")
@@ -99,8 +101,10 @@ class MicroTileImp(outer: MicroTile) extends LazyModuleImp(outer) {
     // Connect external IO
     cpu.module.io.debug <> io.debug(i)
     io.debugRegData(i) <> cpu.module.io.debugRegData
+    io.debugRetired(i) := cpu.module.io.debugRetired
     io.halt(i) := cpu.module.io.halt
     cpu.module.io.timerInterrupt := io.timerInterrupt(i)
     cpu.module.io.softwareInterrupt := io.softwareInterrupt(i)
+    cpu.module.io.externalInterrupt := io.externalInterrupt(i)
   }
 }
@@ -383,6 +383,22 @@ length: 0x10000
     )
   }
 
+  it should "accept TCM with simulator memory attributes" in {
+    val yaml = """type: tcm
+baseAddress: 0x80000000
+length: 0x10000
+readonly: false
+executable: true
+"""
+    val result = parse(yaml).flatMap(_.as[Memory](Config.memoryDecoder))
+    result shouldBe Right(
+      TCM(
+        baseAddress = 0x80000000L,
+        length = 0x10000L
+      )
+    )
+  }
+
   it should "reject Memory with unknown type" in {
     val yaml = """type: DRAM
 baseAddress: 0x80000000
@@ -436,6 +452,48 @@ memories:
     soc.memories(1) shouldBe TCM(0x90000000L, 0x20000L)
   }
 
+  it should "decode an explicit reset vector" in {
+    val yaml = """clusters:
+  - coreType: micro
+    isa: rv32i
+    numCores: 1
+io: []
+memories:
+  - type: tcm
+    baseAddress: 0x80000000
+    length: 0x10000
+  - type: tcm
+    baseAddress: 0x90000000
+    length: 0x10000
+resetVector: 0x00010000
+"""
+    val result = parse(yaml).flatMap(_.as[SoCYaml](Config.socYamlDecoder))
+    result shouldBe a[Right[_, _]]
+    val soc = result.getOrElse(fail("Failed to decode SoC"))
+    soc.resetVector shouldBe Some(0x00010000L)
+    soc.memories.map(_.getBaseAddress) should not contain 0x00010000L
+
+    SoC.fromYaml(soc, simulatorDebug = false).resetVector shouldBe Some(
+      0x00010000L
+    )
+  }
+
+  it should "leave the reset vector unset when omitted" in {
+    val yaml = """clusters:
+  - coreType: micro
+    isa: rv32i
+    numCores: 1
+io: []
+memories:
+  - type: tcm
+    baseAddress: 0x80000000
+    length: 0x10000
+"""
+    val result = parse(yaml).flatMap(_.as[SoCYaml](Config.socYamlDecoder))
+    result shouldBe a[Right[_, _]]
+    result.getOrElse(fail("Failed to decode SoC")).resetVector shouldBe None
+  }
+
   it should "reject SoC with invalid IO type" in {
     val yaml = """clusters:
   - coreType: micro
@@ -1,13 +1,120 @@
 use std::{
     io::{BufRead, BufReader},
-    path::Path,
+    path::{Path, PathBuf},
     process::{Command, Stdio},
+    time::Duration,
 };
 
 use anyhow::{Context, Result};
 
 // Re-export simulator types
-pub use simulator::{Backend, RegisterFile, Simulator, TestResult};
+pub use simulator::{
+    Backend, CycleOutcome, DebugCommand, HaltReason, IsaFeatures, LoadedBinary, RegisterFile,
+    Simulator, StallDetection, StopReason, TestResult,
+};
+
+/// Extra cycles granted per byte of executable code
+const CYCLES_PER_TEXT_BYTE: usize = 16;
+
+/// Cycle budget for a test binary
+///
+/// `SVAROG_MAX_CYCLES` takes precedence when set. Otherwise the budget is
+/// `base` plus `CYCLES_PER_TEXT_BYTE` cycles for every byte of executable code,
+/// so large tests get room to finish while small ones still fail fast. The
+/// heuristic assumes loop-heavy tests have proportionally more code; a tiny
+/// test with a huge loop count still needs the env var.
+pub fn cycle_budget(base: usize, text_size: u64) -> usize {
+    std::env::var("SVAROG_MAX_CYCLES")
+        .ok()
+        .and_then(|val| val.parse::<usize>().ok())
+        .unwrap_or_else(|| {
+            base.saturating_add((text_size as usize).saturating_mul(CYCLES_PER_TEXT_BYTE))
+        })
+}
+
+/// Report how long a simulation took and enforce `SVAROG_TIME_BUDGET_MS`
+///
+/// The duration is always printed; when the budget is set and exceeded the
+/// test fails, so slowdowns in the RTL or harness show up per test.
+pub fn check_time_budget(elapsed: Duration) -> Result<()> {
+    println!("Simulation took {:.2?}", elapsed);
+
+    let budget = std::env::var("SVAROG_TIME_BUDGET_MS")
+        .ok()
+        .and_then(|val| val.parse::<u64>().ok())
+        .map(Duration::from_millis);
+
+    if let Some(budget) = budget
+        && elapsed > budget
+    {
+        anyhow::bail!(
+            "Simulation took {:.2?}, over the {:?} time budget",
+            elapsed,
+            budget
+        );
+    }
+
+    Ok(())
+}
+
+/// VCD trace of a single test that is only kept when it is worth looking at
+///
+/// The simulator writes to a scratch file next to the final location. When the
+/// test fails, or `SVAROG_KEEP_VCD=1` is set, [`VcdTrace::finish`] moves it into
+/// place; otherwise the scratch file is deleted.
+pub struct VcdTrace {
+    scratch: PathBuf,
+    dest: PathBuf,
+}
+
+impl VcdTrace {
+    pub fn new(dest: PathBuf) -> Result<Self> {
+        let dir = dest.parent().unwrap_or(Path::new(".")).join("tmp");
+        std::fs::create_dir_all(&dir).context("Failed to create VCD scratch directory")?;
+        let scratch = dir.join(dest.file_name().context("VCD path has no file name")?);
+
+        Ok(Self { scratch, dest })
+    }
+
+    /// Path the simulator should trace to
+    pub fn path(&self) -> &Path {
+        &self.scratch
+    }
+
+    /// Persist or discard the trace depending on the test outcome
+    pub fn finish(self, failed: bool) {
+        if !self.scratch.exists() {
+            return;
+        }
+
+        let keep = failed || std::env::var("SVAROG_KEEP_VCD").is_ok_and(|val| val == "1");
+        let result = if keep {
+            std::fs::rename(&self.scratch, &self.dest)
+        } else {
+            std::fs::remove_file(&self.scratch)
+        };
+
+        if let Err(e) = result {
+            eprintln!(
+                "Warning: failed to clean up VCD {}: {}",
+                self.scratch.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Spike result together with what its final register values depend on
+pub struct SpikeRun {
+    pub result: TestResult,
+    /// ISA features each register's final value was computed with, following
+    /// register dataflow (values passing through memory are not tracked)
+    pub depends_on: [IsaFeatures; 32],
+    /// PCs of all committed instructions, in order
+    pub commits: Vec<u32>,
+    /// Watchpoint address Spike was asked to stop at
+    pub watchpoint: Option<u32>,
+}
 
 /// Run test in Spike and return register state
 pub fn run_spike_test(
@@ -15,6 +122,15 @@ pub fn run_spike_test(
     watchpoint_addr: Option<u32>,
     isa: &str,
 ) -> Result<TestResult> {
+    Ok(run_spike_traced(elf_path, watchpoint_addr, isa)?.result)
+}
+
+/// Run test in Spike and track the ISA features behind every register value
+pub fn run_spike_traced(
+    elf_path: &Path,
+    watchpoint_addr: Option<u32>,
+    isa: &str,
+) -> Result<SpikeRun> {
     let mut child = Command::new("spike")
         .arg(format!("--isa={isa}"))
         .args(["-l", "--log-commits"])
@@ -30,47 +146,222 @@ pub fn run_spike_test(
         .ok_or_else(|| anyhow::anyhow!("Failed to capture spike stderr"))?;
     let reader = BufReader::new(stderr);
     let mut regs = RegisterFile::new();
+    let mut depends_on = [IsaFeatures::default(); 32];
+    let mut commits = Vec::new();
 
     let mut lines_seen = 0usize;
-    let mut hit_watchpoint = false;
+    let mut instructions_retired = 0u64;
+    let mut stop_reason = StopReason::Exited;
     for line in reader.lines() {
         let line = line?;
         lines_seen += 1;
+        let commit = is_spike_commit(&line);
+        if commit {
+            instructions_retired += 1;
+            if let Some(pc) = line.split_whitespace().nth(3).and_then(parse_hex) {
+                commits.push(pc);
+            }
+        }
         if let Some(reg_write) = parse_spike_reg_write(&line) {
             regs.set(reg_write.0, reg_write.1);
+            if commit && (reg_write.0 as usize) < depends_on.len() {
+                depends_on[reg_write.0 as usize] = parse_spike_inst(&line)
+                    .map(|inst| instruction_dependencies(inst, &depends_on))
+                    .unwrap_or_default();
+            }
         }
 
         if let Some(addr) = parse_spike_mem_write(&line) {
             if Some(addr) == watchpoint_addr {
                 // Test reached tohost; stop spike execution.
                 let _ = child.kill();
-                hit_watchpoint = true;
+                stop_reason = StopReason::Watchpoint;
                 break;
             }
         }
 
         if lines_seen > 1_000_000 {
             let _ = child.kill();
-            anyhow::bail!(
+            eprintln!(
                 "Spike did not reach tohost (addr=0x{:08x}) within log limit",
                 watchpoint_addr.unwrap_or(0)
             );
+            stop_reason = StopReason::Timeout;
+            break;
         }
     }
 
     // Wait for spike to exit (ignore errors)
     let _ = child.wait();
 
-    if watchpoint_addr.is_some() && !hit_watchpoint {
-        anyhow::bail!(
+    if let Some(addr) = watchpoint_addr
+        && stop_reason == StopReason::Exited
+    {
+        eprintln!(
             "Spike terminated without hitting tohost (addr=0x{:08x})",
-            watchpoint_addr.unwrap()
+            addr
         );
     }
 
-    Ok(TestResult {
-        regs,
-        exit_code: None,
+    Ok(SpikeRun {
+        result: TestResult {
+            regs,
+            exit_code: None,
+            stop_reason,
+            instructions_retired,
+        },
+        depends_on,
+        commits,
+        watchpoint: watchpoint_addr,
+    })
+}
+
+/// Instruction word of a spike commit record, the `(0x...)` token
+fn parse_spike_inst(line: &str) -> Option<u32> {
+    line.split_whitespace()
+        .find(|token| token.starts_with("(0x") && token.ends_with(')'))
+        .and_then(parse_hex)
+}
+
+/// Features the result of `inst` depends on: its own plus those of the
+/// source registers it reads
+fn instruction_dependencies(inst: u32, depends_on: &[IsaFeatures; 32]) -> IsaFeatures {
+    let rs1 = ((inst >> 15) & 0x1f) as usize;
+    let rs2 = ((inst >> 20) & 0x1f) as usize;
+    let funct3 = (inst >> 12) & 0x7;
+
+    let (reads_rs1, reads_rs2) = match inst & 0x7f {
+        // OP, OP-32
+        0x33 | 0x3b => (true, true),
+        // OP-IMM, OP-IMM-32, LOAD, JALR
+        0x13 | 0x1b | 0x03 | 0x67 => (true, false),
+        // CSRRW/CSRRS/CSRRC, the immediate forms read no register
+        0x73 => (funct3 != 0 && funct3 < 4, false),
+        _ => (false, false),
+    };
+
+    let mut features = IsaFeatures::of_instruction(inst);
+    if reads_rs1 {
+        features = features.union(depends_on[rs1]);
+    }
+    if reads_rs2 {
+        features = features.union(depends_on[rs2]);
+    }
+    features
+}
+
+/// Whether a spike log line is a commit record
+///
+/// Commit records carry the privilege level after the core id
+/// (`core   0: 3 0x80000000 (0x00000297) ...`), the instruction trace lines
+/// printed by `-l` go straight to the PC.
+fn is_spike_commit(line: &str) -> bool {
+    let mut parts = line.split_whitespace();
+    parts.next() == Some("core")
+        && parts.next().is_some_and(|id| id.ends_with(':'))
+        && parts
+            .next()
+            .is_some_and(|prv| prv.len() == 1 && prv.as_bytes()[0].is_ascii_digit())
+}
+
+/// First instruction where the RTL and Spike commit streams disagree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// RTL cycle (counted from the start of the run) the mismatch was seen at
+    pub cycle: usize,
+    /// Index of the instruction in the commit stream
+    pub index: usize,
+    /// PC Spike committed here, `None` if Spike had already stopped
+    pub expected_pc: Option<u32>,
+    /// PC the RTL retired here, `None` if the RTL stopped first
+    pub actual_pc: Option<u32>,
+}
+
+/// Result of [`cosim`]
+#[derive(Debug)]
+pub struct CosimReport {
+    /// Instructions both sides committed identically
+    pub matched: usize,
+    pub divergence: Option<Divergence>,
+    pub rtl: TestResult,
+    pub spike: TestResult,
+}
+
+/// Run an ELF on the RTL and on Spike and compare them instruction by
+/// instruction
+///
+/// Spike runs first and its commit PCs become the expected stream; the RTL is
+/// then stepped cycle by cycle and every retired PC is checked against it.
+/// The boot ROM instructions Spike runs before reaching the ELF entry point
+/// are skipped. Only PCs are compared per instruction, the RTL does not expose
+/// its register writes, so final register state is left in the report for
+/// [`compare_results`].
+pub fn cosim(
+    elf_path: &Path,
+    backend: Backend,
+    model_name: &str,
+    isa: &str,
+    max_cycles: usize,
+) -> Result<CosimReport> {
+    let simulator = Simulator::new(backend, model_name)?;
+    let loaded = simulator
+        .load_binary(elf_path, &["tohost"])
+        .context("Failed to load binary")?;
+    let tohost_addr = loaded.watchpoints.first().map(|(_, addr)| *addr);
+
+    let spike = run_spike_traced(elf_path, tohost_addr, isa).context("Spike simulation failed")?;
+    let expected: Vec<u32> = spike
+        .commits
+        .iter()
+        .copied()
+        .skip_while(|&pc| pc != loaded.entry_point)
+        .collect();
+
+    let mut stepper = simulator.stepper(None, loaded.entry_point)?;
+    let mut matched = 0;
+    let mut divergence = None;
+    while stepper.cycle() < max_cycles {
+        let outcome = stepper.advance();
+        if let CycleOutcome::Stopped(_) = outcome {
+            break;
+        }
+
+        let Some(pc) = simulator.retired_pc() else {
+            continue;
+        };
+        if expected.get(matched) == Some(&pc) {
+            matched += 1;
+            continue;
+        }
+
+        divergence = Some(Divergence {
+            cycle: stepper.cycle(),
+            index: matched,
+            expected_pc: expected.get(matched).copied(),
+            actual_pc: Some(pc),
+        });
+        break;
+    }
+
+    let cycles = stepper.cycle();
+    if cycles >= max_cycles {
+        stepper.time_out();
+    }
+    let rtl = stepper.finish()?;
+    if divergence.is_none() && rtl.stop_reason != spike.result.stop_reason {
+        divergence = Some(Divergence {
+            cycle: cycles,
+            index: matched,
+            expected_pc: expected.get(matched).copied(),
+            actual_pc: None,
+        });
+    }
+
+    Ok(CosimReport {
+        matched,
+        divergence,
+        rtl,
+        spike: spike.result,
     })
 }
 
@@ -132,30 +423,175 @@ fn parse_spike_mem_write(line: &str) -> Option<u32> {
 }
 
 /// Compare Verilator and Spike results
-pub fn compare_results(verilator: &TestResult, spike: &TestResult) -> Result<()> {
+///
+/// Both runs must have stopped for the same reason, otherwise their register
+/// states are not comparable and the stop-reason mismatch is reported instead.
+/// When a `watchpoint` was requested, Spike must also have stopped at it: two
+/// runs that both spun out their budget in the same loop prove nothing.
+pub fn compare_results(
+    verilator: &TestResult,
+    spike: &TestResult,
+    watchpoint: Option<u32>,
+) -> Result<()> {
+    compare_against(verilator, spike, "Spike", watchpoint.is_some(), |_| true)
+}
+
+/// Compare Verilator and Spike results for a model implementing `features`
+///
+/// Like [`compare_results`], but registers whose Spike value depends on a
+/// feature the model lacks (e.g. a `div` on a Zmmul core, or a CSR read on a
+/// core without Zicsr) are left out of the comparison.
+pub fn compare_results_for(
+    verilator: &TestResult,
+    spike: &SpikeRun,
+    features: &IsaFeatures,
+) -> Result<()> {
+    let skipped: Vec<String> = (1..32)
+        .filter(|&i| !features.covers(&spike.depends_on[i]))
+        .map(|i| format!("x{}", i))
+        .collect();
+    if !skipped.is_empty() {
+        println!(
+            "Not comparing registers using unimplemented features: {}",
+            skipped.join(", ")
+        );
+    }
+
+    compare_against(
+        verilator,
+        &spike.result,
+        "Spike",
+        spike.watchpoint.is_some(),
+        |i| features.covers(&spike.depends_on[i as usize]),
+    )
+}
+
+/// Compare a Verilator result against a golden result saved by
+/// [`TestResult::save`]
+///
+/// Same checks as [`compare_results`], for pinning known-good architectural
+/// state without re-running Spike.
+pub fn compare_with_golden(verilator: &TestResult, golden_path: &Path) -> Result<()> {
+    let golden = TestResult::load(golden_path).context("Failed to load golden result")?;
+    compare_against(verilator, &golden, "golden", false, |_| true)
+}
+
+fn compare_against(
+    verilator: &TestResult,
+    reference: &TestResult,
+    name: &str,
+    watchpoint_requested: bool,
+    compared: impl Fn(u8) -> bool,
+) -> Result<()> {
+    if verilator.stop_reason != reference.stop_reason {
+        anyhow::bail!(
+            "stop-reason mismatch: Verilator stopped with {:?}, {} with {:?}",
+            verilator.stop_reason,
+            name,
+            reference.stop_reason
+        );
+    }
+    if watchpoint_requested && reference.stop_reason != StopReason::Watchpoint {
+        anyhow::bail!(
+            "Neither Verilator nor {} reached the watchpoint, both stopped with {:?}",
+            name,
+            reference.stop_reason
+        );
+    }
+
+    let name = name.to_lowercase();
     let mut mismatches = Vec::new();
 
     // Compare all registers (except x0 which is always 0)
-    for i in 1..32 {
+    for i in (1..32).filter(|&i| compared(i)) {
         let v_val = verilator.regs.get(i);
-        let s_val = spike.regs.get(i);
+        let r_val = reference.regs.get(i);
 
-        if v_val != s_val {
+        if v_val != r_val {
             mismatches.push(format!(
-                "x{}: verilator=0x{:08x}, spike=0x{:08x}",
-                i, v_val, s_val
+                "x{}: verilator=0x{:08x}, {}=0x{:08x}",
+                i, v_val, name, r_val
             ));
         }
     }
 
     if !mismatches.is_empty() {
         anyhow::bail!(
-            "Register mismatches (x30 verilator=0x{:08x}, spike=0x{:08x}):\n{}",
+            "Register mismatches (x30 verilator=0x{:08x}, {}=0x{:08x}):\n{}",
             verilator.regs.get(30),
-            spike.regs.get(30),
+            name,
+            reference.regs.get(30),
             mismatches.join("\n")
         );
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Commit records of `mul a0, a1, a2`, `add a3, a0, x0` and `lui a3, 0x1`
+    const MUL: &str = "core   0: 3 0x80000010 (0x02c58533) x10 0x00000006";
+    const ADD: &str = "core   0: 3 0x80000014 (0x000506b3) x13 0x00000006";
+    const LUI: &str = "core   0: 3 0x80000018 (0x000016b7) x13 0x00001000";
+
+    const MUL_ONLY: IsaFeatures = IsaFeatures {
+        mul: true,
+        div: false,
+        zicsr: false,
+    };
+
+    #[test]
+    fn spike_commit_lines() {
+        assert!(is_spike_commit(MUL));
+        assert!(is_spike_commit(
+            "core   0: 3 0x80000000 (0x00000297) x5  0x80000000"
+        ));
+        // Instruction trace and symbol lines printed by `-l`
+        assert!(!is_spike_commit(
+            "core   0: 0x80000000 (0x00000297) auipc   t0, 0x0"
+        ));
+        assert!(!is_spike_commit("core   0: >>>>  _start"));
+        assert!(!is_spike_commit("x5 0x80000000"));
+    }
+
+    #[test]
+    fn dependencies_follow_source_registers() {
+        let mut depends_on = [IsaFeatures::default(); 32];
+        for line in [MUL, ADD] {
+            let inst = parse_spike_inst(line).unwrap();
+            let rd = ((inst >> 7) & 0x1f) as usize;
+            depends_on[rd] = instruction_dependencies(inst, &depends_on);
+        }
+        assert_eq!(depends_on[10], MUL_ONLY);
+        // add reads a0, which came from mul
+        assert_eq!(depends_on[13], MUL_ONLY);
+
+        // lui reads no register, so overwriting a3 drops the dependency
+        let lui = parse_spike_inst(LUI).unwrap();
+        assert_eq!(
+            instruction_dependencies(lui, &depends_on),
+            IsaFeatures::default()
+        );
+    }
+
+    #[test]
+    fn csr_immediate_reads_no_register() {
+        let mut depends_on = [IsaFeatures::default(); 32];
+        depends_on[5] = MUL_ONLY;
+
+        let zicsr = IsaFeatures {
+            zicsr: true,
+            ..IsaFeatures::default()
+        };
+        // csrrwi x0, mscratch, 5: the 5 sits in the rs1 field
+        assert_eq!(instruction_dependencies(0x3402_d073, &depends_on), zicsr);
+        // csrrw x0, mscratch, x5
+        assert_eq!(
+            instruction_dependencies(0x3402_9073, &depends_on),
+            zicsr.union(MUL_ONLY)
+        );
+    }
+}
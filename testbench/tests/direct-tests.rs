@@ -2,15 +2,54 @@
 //!
 //! Runs handwritten assembly tests from testbench/direct-tests/rv32/
 //! These tests exercise specific hardware features like CLINT interrupts.
+//! The `api::` tests drive the simulator's debug API against a loaded but
+//! halted program instead.
 
 use anyhow::{Context, Result};
 use glob::glob;
 use libtest_mimic::{Arguments, Failed, Trial};
+use std::cell::RefCell;
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use testbench::{Backend, Simulator};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+use testbench::{
+    Backend, CycleOutcome, DebugCommand, HaltReason, LoadedBinary, Simulator, StallDetection,
+    StopReason, VcdTrace, check_time_budget, cosim, cycle_budget,
+};
 
 const TARGET_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../target/");
 
+/// Direct test whose ELF the `api::` tests load to get a halted core
+const API_PROGRAM: &str = "csr_test";
+
+/// Programs that need input from an `api::` test and never pass on their own
+const API_ONLY_PROGRAMS: &[&str] = &["uart_echo", "external_irq"];
+
+type ApiTest = fn(&Simulator, &LoadedBinary) -> Result<()>;
+
+/// Debug API tests, run on every model
+const API_TESTS: &[(&str, ApiTest)] = &[
+    ("write_read_mem", test_write_read_mem),
+    ("read_write_register", test_read_write_register),
+    ("uart_loopback", test_uart_loopback),
+    ("step", test_step),
+    ("cosim", test_cosim),
+    ("instructions_retired", test_instructions_retired),
+    ("validate_misplaced", test_validate_misplaced),
+    ("fst_trace", test_fst_trace),
+    ("external_interrupt", test_external_interrupt),
+    ("hart_addressing", test_hart_addressing),
+    ("data_watchpoint_skipped", test_data_watchpoint_skipped),
+    ("stall_detection", test_stall_detection),
+    ("stop_flag", test_stop_flag),
+    ("run_until_pc", test_run_until_pc),
+    ("peek_registers", test_peek_registers),
+    ("dump_section", test_dump_section),
+];
+
 fn main() -> Result<()> {
     let vcd_path = PathBuf::from(format!("{}/vcd", TARGET_PATH));
     std::fs::create_dir_all(&vcd_path)?;
@@ -45,12 +84,22 @@ fn discover_tests() -> Result<Vec<Trial>> {
             }
 
             let test_name = test_path.file_name().unwrap().to_str().unwrap().to_owned();
+            if API_ONLY_PROGRAMS.contains(&test_name.as_str()) {
+                continue;
+            }
 
             trials.push(Trial::test(
                 format!("{}::{}", model_name, test_name),
                 move || run_test(&test_path, model_name),
             ));
         }
+
+        for &(name, test) in API_TESTS {
+            trials.push(Trial::test(
+                format!("{}::api::{}", model_name, name),
+                move || run_api_test(model_name, test).map_err(|e| format!("{:#}", e).into()),
+            ));
+        }
     }
 
     Ok(trials)
@@ -58,38 +107,43 @@ fn discover_tests() -> Result<Vec<Trial>> {
 
 /// Run a single test case
 fn run_test(test_path: &Path, model_name: &'static str) -> Result<(), Failed> {
-    match run_test_impl(test_path, model_name) {
+    let test_name = test_path.file_name().unwrap().to_str().unwrap().to_owned();
+    let trace = VcdTrace::new(PathBuf::from(format!(
+        "{}/vcd/direct_{}_{}.vcd",
+        TARGET_PATH, model_name, test_name
+    )))
+    .map_err(|e| format!("{:#}", e))?;
+
+    let result = run_test_impl(test_path, model_name, trace.path());
+    trace.finish(result.is_err());
+
+    match result {
         Ok(()) => Ok(()),
         Err(e) => Err(format!("{:#}", e).into()),
     }
 }
 
-fn run_test_impl(test_path: &Path, model_name: &'static str) -> Result<()> {
+fn run_test_impl(test_path: &Path, model_name: &'static str, vcd_path: &Path) -> Result<()> {
     let test_name = test_path.file_name().unwrap().to_str().unwrap().to_owned();
-    let vcd_path = PathBuf::from(format!(
-        "{}/vcd/direct_{}_{}.vcd",
-        TARGET_PATH, model_name, test_name
-    ));
 
     // Create simulator with specified model
     let simulator = Simulator::new(Backend::VerilatorMonitored, model_name)
         .map_err(|e| anyhow::anyhow!("Failed to create simulator: {}", e))?;
 
     // Load the ELF binary with watchpoint on 'tohost' symbol
-    let _tohost_addr = simulator
-        .load_binary(test_path, Some("tohost"))
+    let loaded = simulator
+        .load_binary(test_path, &["tohost"])
         .context("Failed to load binary")?;
 
     // Run simulation with generous cycle limit for interrupt tests
-    let max_cycles = std::env::var("SVAROG_MAX_CYCLES")
-        .ok()
-        .and_then(|val| val.parse::<usize>().ok())
-        .unwrap_or(50_000);
+    let max_cycles = cycle_budget(50_000, loaded.text_size);
 
     println!("Simulating {} on model {}...", test_name, model_name);
+    let start = Instant::now();
     let result = simulator
-        .run(Some(&vcd_path), max_cycles)
+        .run(Some(vcd_path), max_cycles)
         .context("Simulation failed")?;
+    check_time_budget(start.elapsed())?;
     println!("Simulation complete");
 
     // Check test result in gp (x3) register
@@ -109,3 +163,543 @@ fn run_test_impl(test_path: &Path, model_name: &'static str) -> Result<()> {
         anyhow::bail!("Test FAILED at test case {} (gp=0x{:08x})", test_num, gp);
     }
 }
+
+/// Load the API program halted and hand the simulator to `test`
+fn run_api_test(model_name: &'static str, test: ApiTest) -> Result<()> {
+    let simulator = Simulator::new(Backend::VerilatorMonitored, model_name)
+        .map_err(|e| anyhow::anyhow!("Failed to create simulator: {}", e))?;
+    let loaded = simulator
+        .load_binary(direct_test_path(API_PROGRAM), &["tohost"])
+        .context("Failed to load binary")?;
+
+    test(&simulator, &loaded)
+}
+
+/// Path of the built ELF of direct test `name`
+fn direct_test_path(name: &str) -> PathBuf {
+    PathBuf::from(format!("{TARGET_PATH}/direct-tests/rv32/{name}"))
+}
+
+/// First address of the model's writable RAM
+fn ram_base(simulator: &Simulator) -> Result<u32> {
+    simulator
+        .memory_regions()
+        .iter()
+        .find(|region| !region.readonly)
+        .map(|region| region.base as u32)
+        .context("Model has no writable memory")
+}
+
+/// Unaligned head and tail bytes survive a write/read round trip
+fn test_write_read_mem(simulator: &Simulator, _: &LoadedBinary) -> Result<()> {
+    let addr = ram_base(simulator)? + 0x1001;
+    let data = [0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77];
+    let before = simulator.read_mem(addr - 1, data.len() + 2)?;
+
+    simulator.write_mem(addr, &data)?;
+
+    let after = simulator.read_mem(addr - 1, data.len() + 2)?;
+    anyhow::ensure!(
+        after[1..=data.len()] == data,
+        "read back {:02x?}, wrote {:02x?}",
+        &after[1..=data.len()],
+        data
+    );
+    anyhow::ensure!(
+        after[0] == before[0] && after[data.len() + 1] == before[data.len() + 1],
+        "neighbouring bytes changed: {:02x?} -> {:02x?}",
+        before,
+        after
+    );
+    Ok(())
+}
+
+/// Register writes read back, x0 stays zero and out-of-range indices fail
+fn test_read_write_register(simulator: &Simulator, _: &LoadedBinary) -> Result<()> {
+    simulator.write_register(5, 0xdeadbeef)?;
+    let x5 = simulator.read_register(5)?;
+    anyhow::ensure!(x5 == 0xdeadbeef, "x5 read back as 0x{:08x}", x5);
+
+    simulator.write_register(0, 0x1234)?;
+    let x0 = simulator.read_register(0)?;
+    anyhow::ensure!(x0 == 0, "x0 read back as 0x{:08x}", x0);
+
+    anyhow::ensure!(
+        simulator.read_register(32).is_err(),
+        "reading x32 did not fail"
+    );
+    anyhow::ensure!(
+        simulator.write_register(32, 1).is_err(),
+        "writing x32 did not fail"
+    );
+    Ok(())
+}
+
+/// Console sink the test can still read after handing it to the simulator
+#[derive(Clone, Default)]
+struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Bytes queued on UART0 RX come back on TX through the echo program
+fn test_uart_loopback(simulator: &Simulator, _: &LoadedBinary) -> Result<()> {
+    let loaded = simulator
+        .load_binary(direct_test_path("uart_echo"), &["tohost"])
+        .context("Failed to load binary")?;
+
+    let console = SharedBuf::default();
+    simulator.enable_uart_console_to(0, Box::new(console.clone()));
+    simulator.queue_uart_input(0, b"Hi\n")?;
+
+    let result = simulator
+        .run(None, cycle_budget(200_000, loaded.text_size))
+        .context("Simulation failed")?;
+
+    let echoed = console.0.borrow();
+    anyhow::ensure!(
+        echoed.as_slice() == b"Hi\n",
+        "UART0 sent {:?}, expected \"Hi\\n\"",
+        String::from_utf8_lossy(&echoed)
+    );
+    anyhow::ensure!(
+        result.regs.get(3) == 1,
+        "echo program did not pass (gp=0x{:08x})",
+        result.regs.get(3)
+    );
+    Ok(())
+}
+
+/// Each step retires exactly the next instruction of the straight-line
+/// register clearing at `_start` and stops at the one after it
+fn test_step(simulator: &Simulator, loaded: &LoadedBinary) -> Result<()> {
+    simulator.prepare(loaded.entry_point)?;
+
+    for i in 1..=3 {
+        let expected = loaded.entry_point + 4 * i;
+        let pc = simulator.step()?;
+        anyhow::ensure!(
+            pc == Some(expected),
+            "step {} stopped at {:x?}, expected 0x{:08x}",
+            i,
+            pc,
+            expected
+        );
+    }
+    Ok(())
+}
+
+/// The API program retires the same PCs as Spike, starting at the entry point
+fn test_cosim(simulator: &Simulator, loaded: &LoadedBinary) -> Result<()> {
+    let report = cosim(
+        &direct_test_path(API_PROGRAM),
+        Backend::VerilatorMonitored,
+        simulator.model_name(),
+        simulator.isa(),
+        cycle_budget(50_000, loaded.text_size),
+    )?;
+
+    if let Some(divergence) = &report.divergence {
+        anyhow::bail!(
+            "diverged after {} matching instructions: {:?}",
+            report.matched,
+            divergence
+        );
+    }
+    anyhow::ensure!(report.matched > 0, "no instructions were compared");
+    Ok(())
+}
+
+/// Every instruction retired from release is counted: stopping after the
+/// 31 register clearing `li`s at `_start` reports exactly 31
+fn test_instructions_retired(simulator: &Simulator, loaded: &LoadedBinary) -> Result<()> {
+    const CLEARED: u32 = 31;
+    let last = loaded.entry_point + 4 * (CLEARED - 1);
+
+    let mut stepper = simulator.stepper(None, loaded.entry_point)?;
+    while simulator.retired_pc() != Some(last) {
+        anyhow::ensure!(
+            stepper.cycle() < 1_000,
+            "0x{:08x} did not retire within 1000 cycles",
+            last
+        );
+        if let CycleOutcome::Stopped(reason) = stepper.advance() {
+            anyhow::bail!("run stopped before 0x{:08x}: {:?}", last, reason);
+        }
+    }
+
+    let result = stepper.finish()?;
+    anyhow::ensure!(
+        result.instructions_retired == u64::from(CLEARED),
+        "counted {} instructions, expected {}",
+        result.instructions_retired,
+        CLEARED
+    );
+    Ok(())
+}
+
+/// ELF32 section header flag of sections that occupy memory
+const SHF_ALLOC: u32 = 0x2;
+
+/// The fields of an ELF32 section header the tests look at
+struct SectionHeader {
+    /// Offset of the header itself in the file
+    at: usize,
+    name: String,
+    flags: u32,
+    addr: u32,
+    offset: usize,
+    size: usize,
+}
+
+/// Section headers of a little-endian ELF32 file
+fn section_headers(elf: &[u8]) -> Vec<SectionHeader> {
+    let read_u16 = |at: usize| u16::from_le_bytes([elf[at], elf[at + 1]]) as usize;
+    let read_u32 = |at: usize| u32::from_le_bytes(elf[at..at + 4].try_into().unwrap());
+    let shoff = read_u32(0x20) as usize;
+    let shentsize = read_u16(0x2e);
+    let shnum = read_u16(0x30);
+    let strtab = read_u32(shoff + read_u16(0x32) * shentsize + 0x10) as usize;
+
+    (0..shnum)
+        .map(|idx| {
+            let at = shoff + idx * shentsize;
+            let name_start = strtab + read_u32(at) as usize;
+            let name_len = elf[name_start..].iter().position(|&b| b == 0).unwrap();
+            SectionHeader {
+                at,
+                name: String::from_utf8_lossy(&elf[name_start..name_start + name_len]).into(),
+                flags: read_u32(at + 0x08),
+                addr: read_u32(at + 0x0c),
+                offset: read_u32(at + 0x10) as usize,
+                size: read_u32(at + 0x14) as usize,
+            }
+        })
+        .collect()
+}
+
+/// A copy of the API program whose first allocatable section is moved right
+/// past the end of the model's memory fails validation
+fn test_validate_misplaced(simulator: &Simulator, _: &LoadedBinary) -> Result<()> {
+    let elf_path = direct_test_path(API_PROGRAM);
+    simulator
+        .validate_binary(&elf_path, &["tohost"])
+        .context("unmodified program did not validate")?;
+
+    let memory_end = simulator
+        .memory_regions()
+        .iter()
+        .map(|region| region.base + region.length)
+        .max()
+        .context("Model has no memory")?;
+
+    let mut elf = std::fs::read(&elf_path)?;
+    let shdr = section_headers(&elf)
+        .into_iter()
+        .find(|shdr| shdr.flags & SHF_ALLOC != 0)
+        .context("API program has no allocatable section")?;
+    elf[shdr.at + 0x0c..shdr.at + 0x10].copy_from_slice(&(memory_end as u32).to_le_bytes());
+
+    let misplaced = std::env::temp_dir().join(format!(
+        "svarog_misplaced_{}_{}",
+        simulator.model_name(),
+        std::process::id()
+    ));
+    std::fs::write(&misplaced, &elf)?;
+    let result = simulator.validate_binary(&misplaced, &["tohost"]);
+    std::fs::remove_file(&misplaced).ok();
+
+    let Err(err) = result else {
+        anyhow::bail!("section at 0x{:08x} passed validation", memory_end);
+    };
+    anyhow::ensure!(
+        format!("{:#}", err).contains("do not fit"),
+        "unexpected validation error: {:#}",
+        err
+    );
+    Ok(())
+}
+
+/// Type byte of the header block every FST file starts with
+const FST_BLOCK_HEADER: u8 = 0;
+
+/// An `.fst` trace path gets an FST file on FST builds and fails the run up
+/// front on VCD builds
+fn test_fst_trace(simulator: &Simulator, loaded: &LoadedBinary) -> Result<()> {
+    let fst_path = PathBuf::from(format!(
+        "{}/vcd/api_{}.fst",
+        TARGET_PATH,
+        simulator.model_name()
+    ));
+    std::fs::remove_file(&fst_path).ok();
+
+    let result = match simulator.run(Some(&fst_path), cycle_budget(50_000, loaded.text_size)) {
+        Ok(result) => result,
+        Err(err) => {
+            let err = format!("{:#}", err);
+            anyhow::ensure!(
+                err.contains("SVAROG_TRACE_FST"),
+                "unexpected error for an FST trace: {}",
+                err
+            );
+            anyhow::ensure!(!fst_path.exists(), "failed run left {:?} behind", fst_path);
+            return Ok(());
+        }
+    };
+
+    anyhow::ensure!(result.regs.get(3) == 1, "API program did not pass");
+    let trace = std::fs::read(&fst_path).with_context(|| format!("no trace at {:?}", fst_path))?;
+    anyhow::ensure!(
+        trace.first() == Some(&FST_BLOCK_HEADER),
+        "{:?} does not start with an FST header block",
+        fst_path
+    );
+    Ok(())
+}
+
+/// `mcause` of the machine external interrupt
+const MCAUSE_MEI: u32 = 0x8000_000b;
+
+/// Raising line 0 traps hart 0 with MEIE enabled, the handler leaves `mcause`
+/// in s0
+fn test_external_interrupt(simulator: &Simulator, _: &LoadedBinary) -> Result<()> {
+    let loaded = simulator
+        .load_binary(direct_test_path("external_irq"), &["tohost"])
+        .context("Failed to load binary")?;
+
+    simulator.assert_interrupt(0)?;
+    let result = simulator
+        .run(None, cycle_budget(50_000, loaded.text_size))
+        .context("Simulation failed")?;
+    simulator.clear_interrupt(0)?;
+
+    let mcause = result.regs.get(8);
+    anyhow::ensure!(
+        mcause == MCAUSE_MEI,
+        "trap handler saw mcause 0x{:08x}, expected 0x{:08x}",
+        mcause,
+        MCAUSE_MEI
+    );
+    anyhow::ensure!(
+        result.regs.get(3) == 1,
+        "interrupt program did not pass (gp=0x{:08x})",
+        result.regs.get(3)
+    );
+    Ok(())
+}
+
+/// Halt and resume drive the requested hart id into the debug port, and a
+/// command to another hart leaves it addressing the selected one again
+fn test_hart_addressing(simulator: &Simulator, _: &LoadedBinary) -> Result<()> {
+    simulator.enable_debug_log(None);
+
+    for (selected, target) in [(0, 0), (0, 1), (1, 1), (1, 0)] {
+        simulator.select_hart(selected);
+        simulator.halt_hart(target);
+        simulator.resume_hart(target);
+        simulator.halt_hart(target);
+
+        let addressed = simulator.addressed_hart();
+        anyhow::ensure!(
+            addressed == selected,
+            "hart {} selected, but hart {} addressed after commands to hart {}",
+            selected,
+            addressed,
+            target
+        );
+
+        let driven: Vec<u8> = simulator
+            .take_debug_log()
+            .iter()
+            .filter(|txn| matches!(txn.command, DebugCommand::Halt | DebugCommand::Resume))
+            .map(|txn| txn.hart)
+            .collect();
+        anyhow::ensure!(
+            driven == [target; 3],
+            "commands to hart {} drove hart ids {:?}",
+            target,
+            driven
+        );
+    }
+
+    simulator.select_hart(0);
+    Ok(())
+}
+
+/// A second data symbol cannot get the one hardware watchpoint: the load
+/// skips it and the first symbol still stops the run
+fn test_data_watchpoint_skipped(simulator: &Simulator, _: &LoadedBinary) -> Result<()> {
+    let loaded = simulator
+        .load_binary(direct_test_path(API_PROGRAM), &["tohost", "tohost"])
+        .context("load with a data symbol as second watchpoint failed")?;
+
+    let result = simulator
+        .run(None, cycle_budget(50_000, loaded.text_size))
+        .context("Simulation failed")?;
+    anyhow::ensure!(
+        result.stop_reason == StopReason::Watchpoint,
+        "run stopped with {:?}, expected the tohost watchpoint",
+        result.stop_reason
+    );
+    anyhow::ensure!(result.regs.get(3) == 1, "API program did not pass");
+    Ok(())
+}
+
+/// `jal x0, 0`, the `1: j 1b` every program spins in once it is done
+const SPIN: u32 = 0x0000_006f;
+
+/// Without a watchpoint the API program ends up spinning on one PC, which
+/// stall detection reports instead of running out the cycle budget
+fn test_stall_detection(simulator: &Simulator, _: &LoadedBinary) -> Result<()> {
+    let loaded = simulator
+        .load_binary(direct_test_path(API_PROGRAM), &[])
+        .context("Failed to load binary")?;
+    simulator.set_stall_detection(Some(StallDetection {
+        sample_interval: 7,
+        threshold: 4,
+    }));
+    simulator.prepare(loaded.entry_point)?;
+
+    let max_cycles = 10_000;
+    let reason = simulator.run_until_pc(u32::MAX, max_cycles);
+    simulator.set_stall_detection(None);
+
+    let HaltReason::Stalled { pc } = reason? else {
+        anyhow::bail!("run did not stall within {} cycles", max_cycles);
+    };
+    let inst = simulator.read_mem_word(pc)?;
+    anyhow::ensure!(
+        inst == SPIN,
+        "stalled at 0x{:08x} on 0x{:08x}, not a spin loop",
+        pc,
+        inst
+    );
+    Ok(())
+}
+
+/// Raising the stop flag ends a run at the next cycle, before the program
+/// reaches its watchpoint
+fn test_stop_flag(simulator: &Simulator, loaded: &LoadedBinary) -> Result<()> {
+    let stop = Arc::new(AtomicBool::new(false));
+    simulator.set_stop_flag(Some(stop.clone()));
+
+    let mut stepper = simulator.stepper(None, loaded.entry_point)?;
+    for _ in 0..5 {
+        stepper.advance();
+    }
+    stop.store(true, Ordering::Relaxed);
+    let outcome = stepper.advance();
+    let ran = stepper.cycle();
+    let result = stepper.finish()?;
+    simulator.set_stop_flag(None);
+
+    anyhow::ensure!(
+        outcome == CycleOutcome::Stopped(HaltReason::Interrupted),
+        "cycle after raising the flag gave {:?}",
+        outcome
+    );
+    anyhow::ensure!(ran == 5, "stopped after {} cycles, expected 5", ran);
+    anyhow::ensure!(
+        result.stop_reason == StopReason::Interrupted,
+        "run stopped with {:?}, expected Interrupted",
+        result.stop_reason
+    );
+    Ok(())
+}
+
+/// Running until the `li x5, 0` at `_start` clears x5 but stops before the
+/// `li x6, 0` after it
+fn test_run_until_pc(simulator: &Simulator, loaded: &LoadedBinary) -> Result<()> {
+    simulator.prepare(loaded.entry_point)?;
+    simulator.write_register(5, 0x5555_5555)?;
+    simulator.write_register(6, 0x6666_6666)?;
+
+    let target = loaded.entry_point + 4 * 4;
+    let reason = simulator.run_until_pc(target, 1_000)?;
+    anyhow::ensure!(
+        reason == HaltReason::Halted,
+        "run to 0x{:08x} ended with {:?}",
+        target,
+        reason
+    );
+
+    let x5 = simulator.read_register(5)?;
+    let x6 = simulator.read_register(6)?;
+    anyhow::ensure!(x5 == 0, "x5 = 0x{:08x}, the target did not retire", x5);
+    anyhow::ensure!(
+        x6 == 0x6666_6666,
+        "x6 = 0x{:08x}, the core ran past the target",
+        x6
+    );
+    Ok(())
+}
+
+/// Peeking shows register writes on the halted core and refuses a running one
+fn test_peek_registers(simulator: &Simulator, loaded: &LoadedBinary) -> Result<()> {
+    simulator.prepare(loaded.entry_point)?;
+    simulator.write_register(7, 0x7777_7777)?;
+
+    let regs = simulator.peek_registers()?;
+    anyhow::ensure!(
+        regs.get(7) == 0x7777_7777,
+        "peeked x7 = 0x{:08x}",
+        regs.get(7)
+    );
+    // A peek leaves the core halted, so it can be repeated
+    let again = simulator.peek_registers()?;
+    anyhow::ensure!(again.get(7) == 0x7777_7777, "second peek of x7 differs");
+
+    // Once instructions retire the core is running
+    let mut stepper = simulator.stepper(None, loaded.entry_point)?;
+    stepper.advance();
+    while simulator.retired_pc().is_none() && stepper.cycle() < 100 {
+        stepper.advance();
+    }
+    let running = simulator.peek_registers();
+    stepper.finish()?;
+    anyhow::ensure!(running.is_err(), "peek on a running core did not fail");
+    Ok(())
+}
+
+/// Right after loading, a dumped `.text` matches the section in the ELF file
+/// byte for byte, and unknown sections are rejected
+fn test_dump_section(simulator: &Simulator, _: &LoadedBinary) -> Result<()> {
+    let elf_path = direct_test_path(API_PROGRAM);
+    let elf = std::fs::read(&elf_path)?;
+    let text = section_headers(&elf)
+        .into_iter()
+        .find(|shdr| shdr.name == ".text")
+        .context("API program has no .text")?;
+
+    let dumped = simulator.dump_section(&elf_path, ".text")?;
+    let expected = &elf[text.offset..text.offset + text.size];
+    if dumped != expected {
+        let first = dumped
+            .iter()
+            .zip(expected)
+            .position(|(a, b)| a != b)
+            .unwrap_or(dumped.len().min(expected.len()));
+        anyhow::bail!(
+            ".text differs from the ELF at 0x{:08x} ({} bytes dumped, {} expected)",
+            text.addr as usize + first,
+            dumped.len(),
+            expected.len()
+        );
+    }
+
+    anyhow::ensure!(
+        simulator
+            .dump_section(&elf_path, ".no_such_section")
+            .is_err(),
+        "dumping a missing section did not fail"
+    );
+    Ok(())
+}
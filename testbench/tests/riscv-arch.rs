@@ -2,7 +2,11 @@ use anyhow::{Context, Result};
 use glob::glob;
 use libtest_mimic::{Arguments, Failed, Trial};
 use std::path::{Path, PathBuf};
-use testbench::{Backend, Simulator, compare_results, run_spike_test};
+use std::time::Instant;
+use testbench::{
+    Backend, Simulator, VcdTrace, check_time_budget, compare_results_for, cycle_budget,
+    run_spike_traced,
+};
 
 const TARGET_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../target/");
 
@@ -23,12 +27,24 @@ fn discover_tests() -> Result<Vec<Trial>> {
     let backend = Backend::Verilator;
     let models = Simulator::available_models(backend);
     let suites = ["I", "M"];
-
-    // Maximum binary size that can fit in RAM (64KB = 65536 bytes)
-    const MAX_BINARY_SIZE: u64 = 64 * 1024;
+    // Only models with multiplication can run the M suite, and only those
+    // with division (M rather than Zmmul) its div/rem tests
+    let mul_models = Simulator::available_models_with(backend, |model| model.features().mul);
+    let div_models = Simulator::available_models_with(backend, |model| model.features().div);
 
     for &model_name in models {
+        // Largest binary that can fit in the model's writable memory
+        let max_binary_size: u64 = Simulator::model_memory_regions(backend, model_name)
+            .unwrap_or_default()
+            .iter()
+            .filter(|region| !region.readonly)
+            .map(|region| region.length)
+            .sum();
+
         for suite in suites {
+            if suite == "M" && !mul_models.contains(&model_name) {
+                continue;
+            }
             let pattern = format!("{TARGET_PATH}/riscv-arch-test/rv32i_m/{suite}/*.elf");
             for test_path in glob(&pattern)? {
                 let test_path = test_path?;
@@ -43,7 +59,7 @@ fn discover_tests() -> Result<Vec<Trial>> {
                     .context("Failed to get file metadata")?
                     .len();
 
-                if file_size > MAX_BINARY_SIZE {
+                if file_size > max_binary_size {
                     // Create an ignored test with a reason
                     trials.push(
                         Trial::test(
@@ -53,17 +69,17 @@ fn discover_tests() -> Result<Vec<Trial>> {
                         .with_ignored_flag(true)
                         .with_kind(format!(
                             "binary too large: {} bytes (max {})",
-                            file_size, MAX_BINARY_SIZE
+                            file_size, max_binary_size
                         )),
                     );
-                } else if test_name.contains("rem") || test_name.contains("div") {
+                } else if uses_division(&test_name) && !div_models.contains(&model_name) {
                     trials.push(
                         Trial::test(
                             format!("{}::arch::{}::{}", model_name, suite, test_name),
                             || Ok(()),
                         )
                         .with_ignored_flag(true)
-                        .with_kind("Division is not synthesizable for now"),
+                        .with_kind("model has no division"),
                     );
                 } else {
                     trials.push(Trial::test(
@@ -78,6 +94,11 @@ fn discover_tests() -> Result<Vec<Trial>> {
     Ok(trials)
 }
 
+/// Whether an M suite test exercises `div*`/`rem*`, e.g. `divu-01`
+fn uses_division(test_name: &str) -> bool {
+    test_name.starts_with("div") || test_name.starts_with("rem")
+}
+
 /// Run a single test case.
 fn run_test(
     test_path: &Path,
@@ -85,7 +106,17 @@ fn run_test(
     model_name: &'static str,
     suite: &str,
 ) -> Result<(), Failed> {
-    match run_test_impl(test_path, backend, model_name, suite) {
+    let test_name = test_path.file_stem().unwrap().to_str().unwrap().to_owned();
+    let trace = VcdTrace::new(PathBuf::from(format!(
+        "{}/vcd/arch_{}_{}_{}.vcd",
+        TARGET_PATH, model_name, suite, test_name
+    )))
+    .map_err(|e| format!("{:#}", e))?;
+
+    let result = run_test_impl(test_path, backend, model_name, suite, trace.path());
+    trace.finish(result.is_err());
+
+    match result {
         Ok(()) => Ok(()),
         Err(e) => Err(format!("{:#}", e).into()),
     }
@@ -96,40 +127,29 @@ fn run_test_impl(
     backend: Backend,
     model_name: &'static str,
     suite: &str,
+    vcd_path: &Path,
 ) -> Result<()> {
     let test_name = test_path.file_stem().unwrap().to_str().unwrap().to_owned();
-    let vcd_path = PathBuf::from(format!(
-        "{}/vcd/arch_{}_{}_{}.vcd",
-        TARGET_PATH, model_name, suite, test_name
-    ));
 
     let simulator = Simulator::new(backend, model_name)
         .map_err(|e| anyhow::anyhow!("Failed to create simulator: {}", e))?;
 
-    let tohost_addr = simulator
-        .load_binary(test_path, Some("tohost"))
+    let loaded = simulator
+        .load_binary(test_path, &["tohost"])
         .context("Failed to load binary")?;
+    let tohost_addr = loaded.watchpoints.first().map(|(_, addr)| *addr);
 
-    let max_cycles = std::env::var("SVAROG_MAX_CYCLES")
-        .ok()
-        .and_then(|val| val.parse::<usize>().ok())
-        .unwrap_or(50_000);
+    let max_cycles = cycle_budget(50_000, loaded.text_size);
 
     println!("Simulating {} on model {}...", test_name, model_name);
+    let start = Instant::now();
     let verilator_result = simulator
-        .run(Some(&vcd_path), max_cycles)
+        .run(Some(vcd_path), max_cycles)
         .context("Verilator simulation failed")?;
+    check_time_budget(start.elapsed())?;
     println!("Simulation complete, capturing registers");
 
-    let mut has_activity = false;
-    for i in 1..32 {
-        if verilator_result.regs.get(i) != 0 {
-            has_activity = true;
-            break;
-        }
-    }
-
-    if !has_activity {
+    if !verilator_result.has_register_activity() {
         anyhow::bail!(
             "No register writes detected from Verilator. \
             CPU may not be completing writeback stage."
@@ -138,10 +158,10 @@ fn run_test_impl(
 
     let isa = if suite == "M" { "RV32IM" } else { "RV32I" };
     println!("Running Spike for {}", test_name);
-    let spike_result =
-        run_spike_test(test_path, tohost_addr, isa).context("Spike simulation failed")?;
+    let spike_run =
+        run_spike_traced(test_path, tohost_addr, isa).context("Spike simulation failed")?;
 
     println!("Comparing architectural state");
-    compare_results(&verilator_result, &spike_result)?;
+    compare_results_for(&verilator_result, &spike_run, &simulator.isa_features())?;
     Ok(())
 }
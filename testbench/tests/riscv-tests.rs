@@ -2,7 +2,10 @@ use anyhow::{Context, Result};
 use glob::glob;
 use libtest_mimic::{Arguments, Failed, Trial};
 use std::path::{Path, PathBuf};
-use testbench::{Backend, Simulator, compare_results, run_spike_test};
+use std::time::Instant;
+use testbench::{
+    Backend, Simulator, VcdTrace, check_time_budget, compare_results, cycle_budget, run_spike_test,
+};
 
 const TARGET_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../target/");
 
@@ -52,50 +55,53 @@ fn discover_tests() -> Result<Vec<Trial>> {
 
 /// Run a single test case
 fn run_test(test_path: &Path, backend: Backend, model_name: &'static str) -> Result<(), Failed> {
-    match run_test_impl(test_path, backend, model_name) {
+    let test_name = test_path.file_name().unwrap().to_str().unwrap().to_owned();
+    let trace = VcdTrace::new(PathBuf::from(format!(
+        "{}/vcd/{}_{}.vcd",
+        TARGET_PATH, model_name, test_name
+    )))
+    .map_err(|e| format!("{:#}", e))?;
+
+    let result = run_test_impl(test_path, backend, model_name, trace.path());
+    trace.finish(result.is_err());
+
+    match result {
         Ok(()) => Ok(()),
         Err(e) => Err(format!("{:#}", e).into()),
     }
 }
 
-fn run_test_impl(test_path: &Path, backend: Backend, model_name: &'static str) -> Result<()> {
+fn run_test_impl(
+    test_path: &Path,
+    backend: Backend,
+    model_name: &'static str,
+    vcd_path: &Path,
+) -> Result<()> {
     let test_name = test_path.file_name().unwrap().to_str().unwrap().to_owned();
-    let vcd_path = PathBuf::from(format!(
-        "{}/vcd/{}_{}.vcd",
-        TARGET_PATH, model_name, test_name
-    ));
 
     // Create simulator with specified model
     let simulator = Simulator::new(backend, model_name)
         .map_err(|e| anyhow::anyhow!("Failed to create simulator: {}", e))?;
 
     // Load the ELF binary with watchpoint on 'tohost' symbol
-    let tohost_addr = simulator
-        .load_binary(test_path, Some("tohost"))
+    let loaded = simulator
+        .load_binary(test_path, &["tohost"])
         .context("Failed to load binary")?;
+    let tohost_addr = loaded.watchpoints.first().map(|(_, addr)| *addr);
 
     // Run Verilator simulation
-    let max_cycles = std::env::var("SVAROG_MAX_CYCLES")
-        .ok()
-        .and_then(|val| val.parse::<usize>().ok())
-        .unwrap_or(20_000);
+    let max_cycles = cycle_budget(20_000, loaded.text_size);
 
     println!("Simulating {} on model {}...", test_name, model_name);
+    let start = Instant::now();
     let verilator_result = simulator
-        .run(Some(&vcd_path), max_cycles)
+        .run(Some(vcd_path), max_cycles)
         .context("Verilator simulation failed")?;
+    check_time_budget(start.elapsed())?;
     println!("Simulation complete, capturing registers");
 
     // Check if there was any register activity
-    let mut has_activity = false;
-    for i in 1..32 {
-        if verilator_result.regs.get(i) != 0 {
-            has_activity = true;
-            break;
-        }
-    }
-
-    if !has_activity {
+    if !verilator_result.has_register_activity() {
         anyhow::bail!(
             "No register writes detected from Verilator. \
             CPU may not be completing writeback stage."
@@ -108,6 +114,6 @@ fn run_test_impl(test_path: &Path, backend: Backend, model_name: &'static str) -
         run_spike_test(test_path, tohost_addr, "RV32I").context("Spike simulation failed")?;
 
     println!("Comparing architectural state");
-    compare_results(&verilator_result, &spike_result)?;
+    compare_results(&verilator_result, &spike_result, tohost_addr)?;
     Ok(())
 }
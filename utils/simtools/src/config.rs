@@ -18,6 +18,7 @@ pub struct Io {
     name: String,
     #[serde(rename = "baseAddr")]
     base_addr: String,
+    baud: Option<u32>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -28,11 +29,28 @@ pub struct Memory {
     #[serde(rename = "baseAddress")]
     base_addr: String,
     length: u64,
+    #[serde(default)]
+    readonly: bool,
+    #[serde(default)]
+    executable: bool,
+}
+
+/// Address range and attributes of a memory described by the config
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub base: u64,
+    pub length: u64,
+    pub readonly: bool,
+    pub executable: bool,
 }
 
 #[derive(Deserialize, Debug, Clone)]
 #[allow(dead_code)]
 pub struct Config {
+    #[serde(rename = "clockHz")]
+    clock_hz: Option<u64>,
+    #[serde(rename = "resetVector")]
+    reset_vector: Option<String>,
     clusters: Vec<Cluster>,
     io: Vec<Io>,
     memories: Vec<Memory>,
@@ -54,4 +72,151 @@ impl Config {
     pub fn num_uarts(&self) -> usize {
         self.io.iter().filter(|io| io.ty == "uart").count()
     }
+
+    /// Address ranges of all configured memories, in config order
+    pub fn memory_regions(&self) -> anyhow::Result<Vec<MemoryRegion>> {
+        self.memories
+            .iter()
+            .map(|memory| {
+                let base = parse_addr(&memory.base_addr).ok_or_else(|| {
+                    anyhow::anyhow!("Invalid memory base address: {}", memory.base_addr)
+                })?;
+                Ok(MemoryRegion {
+                    base,
+                    length: memory.length,
+                    readonly: memory.readonly,
+                    executable: memory.executable,
+                })
+            })
+            .collect()
+    }
+
+    /// Address the cores start executing from after reset
+    ///
+    /// Uses `resetVector` when the config has one, otherwise the base of the
+    /// first memory, which is where the hardware boots without a boot ROM.
+    pub fn reset_vector(&self) -> anyhow::Result<u64> {
+        if let Some(reset_vector) = &self.reset_vector {
+            return parse_addr(reset_vector)
+                .ok_or_else(|| anyhow::anyhow!("Invalid reset vector: {}", reset_vector));
+        }
+
+        self.memory_regions()?
+            .first()
+            .map(|region| region.base)
+            .ok_or_else(|| anyhow::anyhow!("Config has no resetVector and no memories"))
+    }
+
+    /// Core clock cycles per serial bit of the UART at `index`
+    ///
+    /// The UART divider for a given baud rate is `clock_hz / baud` and each bit
+    /// lasts `divider + 1` cycles. Returns `None` when the config does not
+    /// specify the clock or the UART's baud rate.
+    pub fn uart_bit_period(&self, index: usize) -> Option<u32> {
+        let uart = self.io.iter().filter(|io| io.ty == "uart").nth(index)?;
+        let baud = uart.baud.filter(|&baud| baud != 0)?;
+        let divider = self.clock_hz? / baud as u64;
+        u32::try_from(divider + 1).ok()
+    }
+}
+
+/// Parse a config address, either `0x` prefixed hex or decimal
+fn parse_addr(value: &str) -> Option<u64> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "
+clockHz: 50000000
+clusters:
+  - coreType: micro
+    isa: rv32i_zicsr
+    numCores: 1
+io:
+  - type: uart
+    name: uart0
+    baseAddr: 0x00100000
+    baud: 115200
+memories:
+  - type: rom
+    baseAddress: 0x00001000
+    length: 4096
+    readonly: true
+    executable: true
+  - type: tcm
+    baseAddress: 2147483648
+    length: 65536
+";
+
+    #[test]
+    fn memory_regions_in_config_order() {
+        let config: Config = yaml_serde::from_str(CONFIG).unwrap();
+        assert_eq!(
+            config.memory_regions().unwrap(),
+            vec![
+                MemoryRegion {
+                    base: 0x1000,
+                    length: 4096,
+                    readonly: true,
+                    executable: true,
+                },
+                MemoryRegion {
+                    base: 0x8000_0000,
+                    length: 65536,
+                    readonly: false,
+                    executable: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn memory_regions_reject_bad_base() {
+        let config: Config =
+            yaml_serde::from_str(&CONFIG.replace("0x00001000", "0x1000_zz")).unwrap();
+        let err = config.memory_regions().unwrap_err();
+        assert!(err.to_string().contains("0x1000_zz"), "{err}");
+    }
+
+    #[test]
+    fn reset_vector_explicit() {
+        // The reset vector need not be the base of any memory
+        let config: Config = yaml_serde::from_str(&CONFIG.replace(
+            "clockHz: 50000000",
+            "clockHz: 50000000\nresetVector: 0x00001100",
+        ))
+        .unwrap();
+        assert_eq!(config.reset_vector().unwrap(), 0x1100);
+    }
+
+    #[test]
+    fn reset_vector_defaults_to_first_memory() {
+        let config: Config = yaml_serde::from_str(CONFIG).unwrap();
+        assert_eq!(config.reset_vector().unwrap(), 0x1000);
+    }
+
+    #[test]
+    fn uart_bit_period_from_clock_and_baud() {
+        let config: Config = yaml_serde::from_str(CONFIG).unwrap();
+        // 50 MHz / 115200 = 434, plus the cycle the divider counts down to
+        assert_eq!(config.uart_bit_period(0), Some(435));
+        assert_eq!(config.uart_bit_period(1), None);
+    }
+
+    #[test]
+    fn uart_bit_period_needs_clock_and_baud() {
+        let no_clock: Config =
+            yaml_serde::from_str(&CONFIG.replace("clockHz: 50000000\n", "")).unwrap();
+        assert_eq!(no_clock.uart_bit_period(0), None);
+
+        let no_baud: Config =
+            yaml_serde::from_str(&CONFIG.replace("    baud: 115200\n", "")).unwrap();
+        assert_eq!(no_baud.uart_bit_period(0), None);
+    }
 }
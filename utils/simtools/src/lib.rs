@@ -2,7 +2,10 @@ mod config;
 mod utils;
 mod verilator;
 
-pub use config::Config;
-pub use verilator::{GeneratedVerilator, generate_verilator, generate_verilator_with_monitors};
+pub use config::{Config, MemoryRegion};
+pub use verilator::{
+    GeneratedVerilator, TRACE_FST_ENV, generate_verilator, generate_verilator_with_monitors,
+    trace_fst_enabled,
+};
 
 pub use utils::clone_repo;
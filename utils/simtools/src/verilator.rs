@@ -4,24 +4,41 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use quote::{TokenStreamExt, format_ident, quote};
+use quote::{ToTokens, TokenStreamExt, format_ident, quote};
 use xshell::{Shell, cmd};
 
-use crate::config::Config;
+use crate::config::{Config, MemoryRegion};
 
 pub struct GeneratedVerilator {
     pub model_name: String,
     pub model_identifier: String,
     pub wrapper_name: String,
+    /// ISA string of the model's first cluster
+    pub isa: String,
+    /// Memories of the model, in config order
+    pub memory_regions: Vec<MemoryRegion>,
     pub rust: proc_macro2::TokenStream,
     pub verilator_output: PathBuf,
 }
 
+/// Build option that switches the models from VCD to FST tracing
+///
+/// Verilator models trace a single format, so with `SVAROG_TRACE_FST=1` the
+/// wrappers provide `open_fst`/`dump_fst`/`close_fst` in place of the VCD
+/// trio and the models need zlib at link time.
+pub const TRACE_FST_ENV: &str = "SVAROG_TRACE_FST";
+
+/// Whether [`TRACE_FST_ENV`] is set for this build
+pub fn trace_fst_enabled() -> bool {
+    std::env::var(TRACE_FST_ENV).is_ok_and(|val| val == "1")
+}
+
 pub fn generate_verilator(config_path: &Path) -> anyhow::Result<GeneratedVerilator> {
     generate_verilator_with_options(
         config_path,
         VerilatorOptions {
             with_monitors: false,
+            trace_fst: trace_fst_enabled(),
         },
     )
 }
@@ -31,12 +48,15 @@ pub fn generate_verilator_with_monitors(config_path: &Path) -> anyhow::Result<Ge
         config_path,
         VerilatorOptions {
             with_monitors: true,
+            trace_fst: trace_fst_enabled(),
         },
     )
 }
 
 struct VerilatorOptions {
     with_monitors: bool,
+    /// Trace with `VerilatedFstC` instead of `VerilatedVcdC`
+    trace_fst: bool,
 }
 
 fn generate_verilator_with_options(
@@ -54,7 +74,7 @@ fn generate_verilator_with_options(
     };
     let wrapper_model_name = format!("{model_name}{wrapper_suffix}");
     let model_identifier = wrapper_model_name.replace("-", "_");
-    let verilator_output = build_verilator(config_path, &model_identifier, options.with_monitors)?;
+    let verilator_output = build_verilator(config_path, &model_identifier, &options)?;
 
     let file = File::open(config_path)?;
     let config: Config = yaml_serde::from_reader(file)?;
@@ -73,6 +93,51 @@ fn generate_verilator_with_options(
     let xlen = config.xlen();
     let isa = config.isa().unwrap_or("rv32i").to_string();
     let num_uarts = config.num_uarts();
+    let memory_regions = config.memory_regions()?;
+    let reset_vector = u32::try_from(config.reset_vector()?)
+        .map_err(|_| anyhow::anyhow!("Reset vector does not fit in 32 bits"))?;
+    let uart_bit_period_arms = (0..num_uarts).filter_map(|i| {
+        config
+            .uart_bit_period(i)
+            .map(|period| quote! { #i => Some(#period), })
+    });
+
+    let trace_fst = options.trace_fst;
+    let trace_prefix = if trace_fst { "fst" } else { "vcd" };
+    let open_trace = format_ident!("open_{}", trace_prefix);
+    let dump_trace = format_ident!("dump_{}", trace_prefix);
+    let close_trace = format_ident!("close_{}", trace_prefix);
+    let trace_methods = quote! {
+        fn #open_trace(&self, path: &str) {
+            self.model.borrow_mut().pin_mut().#open_trace(path);
+        }
+
+        fn #dump_trace(&self, timestamp: u64) {
+            self.model.borrow_mut().pin_mut().#dump_trace(timestamp);
+        }
+
+        fn #close_trace(&self) {
+            self.model.borrow_mut().pin_mut().#close_trace();
+        }
+    };
+    // The model was built without the other format, those calls are dropped
+    let trace_impl = if trace_fst {
+        quote! {
+            #trace_methods
+
+            fn open_vcd(&self, _path: &str) {}
+            fn dump_vcd(&self, _timestamp: u64) {}
+            fn close_vcd(&self) {}
+        }
+    } else {
+        quote! {
+            #trace_methods
+
+            fn open_fst(&self, _path: &str) {}
+            fn dump_fst(&self, _timestamp: u64) {}
+            fn close_fst(&self) {}
+        }
+    };
 
     let mut uart_bridge = quote! {};
     for i in 0..num_uarts {
@@ -110,7 +175,7 @@ fn generate_verilator_with_options(
 
         use cxx::UniquePtr;
 
-        use crate::core::SimulatorImpl;
+        use crate::core::{MemoryRegion, SimulatorImpl};
 
         #[cxx::bridge(namespace = #namespace)]
         pub mod #ffi_ident {
@@ -122,9 +187,9 @@ fn generate_verilator_with_options(
 
                 fn #factory_fn() -> UniquePtr<#verilator_type>;
 
-                fn open_vcd(self: Pin<&mut #verilator_type>, path: &str);
-                fn dump_vcd(self: Pin<&mut #verilator_type>, timestamp: u64);
-                fn close_vcd(self: Pin<&mut #verilator_type>);
+                fn #open_trace(self: Pin<&mut #verilator_type>, path: &str);
+                fn #dump_trace(self: Pin<&mut #verilator_type>, timestamp: u64);
+                fn #close_trace(self: Pin<&mut #verilator_type>);
 
                 fn eval(self: Pin<&mut #verilator_type>);
                 fn final_eval(self: Pin<&mut #verilator_type>);
@@ -196,6 +261,12 @@ fn generate_verilator_with_options(
 
                 fn get_debug_halted(&self) -> u8;
 
+                fn get_debug_retired_valid(&self) -> u8;
+                fn get_debug_retired_bits(&self) -> u32;
+
+                fn get_debug_irq(&self) -> u32;
+                fn set_debug_irq(self: Pin<&mut #verilator_type>, value: u32);
+
                 #uart_bridge
             }
         }
@@ -225,6 +296,21 @@ fn generate_verilator_with_options(
                 #model_name
             }
 
+            fn reset_vector(&self) -> u32 {
+                #reset_vector
+            }
+
+            fn memory_regions(&self) -> &'static [MemoryRegion] {
+                &[#(#memory_regions),*]
+            }
+
+            fn uart_bit_period(&self, index: usize) -> Option<u32> {
+                match index {
+                    #(#uart_bit_period_arms)*
+                    _ => None,
+                }
+            }
+
             fn eval(&self) {
                 self.model.borrow_mut().pin_mut().eval();
             }
@@ -233,17 +319,11 @@ fn generate_verilator_with_options(
                 self.model.borrow_mut().pin_mut().final_eval();
             }
 
-            fn open_vcd(&self, path: &str) {
-                self.model.borrow_mut().pin_mut().open_vcd(path);
+            fn has_fst(&self) -> bool {
+                #trace_fst
             }
 
-            fn dump_vcd(&self, timestamp: u64) {
-                self.model.borrow_mut().pin_mut().dump_vcd(timestamp);
-            }
-
-            fn close_vcd(&self) {
-                self.model.borrow_mut().pin_mut().close_vcd();
-            }
+            #trace_impl
 
             fn get_clock(&self) -> u8 {
                 self.model.borrow().get_clock()
@@ -512,6 +592,22 @@ fn generate_verilator_with_options(
                 self.model.borrow().get_debug_halted()
             }
 
+            fn get_debug_retired_valid(&self) -> u8 {
+                self.model.borrow().get_debug_retired_valid()
+            }
+
+            fn get_debug_retired_bits(&self) -> u64 {
+                self.model.borrow().get_debug_retired_bits() as u64
+            }
+
+            fn get_debug_irq(&self) -> u32 {
+                self.model.borrow().get_debug_irq()
+            }
+
+            fn set_debug_irq(&self, value: u32) {
+                self.model.borrow_mut().pin_mut().set_debug_irq(value);
+            }
+
             fn get_uart_0_txd(&self) -> u8 {
                 #uart0_get
             }
@@ -535,6 +631,7 @@ fn generate_verilator_with_options(
         &verilator_type.to_string(),
         &factory_fn.to_string(),
         num_uarts,
+        trace_fst,
     );
     let mut cpp_header_file = File::create(header_path)?;
     cpp_header_file.write_all(cpp_header.as_bytes())?;
@@ -543,15 +640,37 @@ fn generate_verilator_with_options(
         model_name: model_name.to_string(),
         model_identifier,
         wrapper_name: struct_name.to_string(),
+        isa,
+        memory_regions,
         rust: tokens,
         verilator_output,
     })
 }
 
+/// Emits the simulator's `MemoryRegion` for the region
+impl ToTokens for MemoryRegion {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        let MemoryRegion {
+            base,
+            length,
+            readonly,
+            executable,
+        } = *self;
+        tokens.append_all(quote! {
+            crate::core::MemoryRegion {
+                base: #base,
+                length: #length,
+                readonly: #readonly,
+                executable: #executable,
+            }
+        });
+    }
+}
+
 fn build_verilator(
     config_path: &Path,
     model_identifier: &str,
-    with_monitors: bool,
+    options: &VerilatorOptions,
 ) -> anyhow::Result<PathBuf> {
     let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR")?)
         .parent()
@@ -566,13 +685,18 @@ fn build_verilator(
     let sh = Shell::new().unwrap();
     sh.change_dir(manifest_dir);
 
-    if with_monitors {
+    if options.with_monitors {
         cmd!(sh, "./mill -i svarog.runMain svarog.VerilogGenerator --simulator-debug-iface=true --with-monitors=true --target-dir={out_path} --config={config_path}").run()?;
     } else {
         cmd!(sh, "./mill -i svarog.runMain svarog.VerilogGenerator --simulator-debug-iface=true --target-dir={out_path} --config={config_path}").run()?;
     }
 
     let verilog_file = out_path.join("SvarogSoC.sv");
+    let trace_flag = if options.trace_fst {
+        "--trace-fst"
+    } else {
+        "--trace"
+    };
     let verilator_output = out_path.join("verilated");
 
     cmd!(
@@ -582,7 +706,7 @@ fn build_verilator(
          -Wno-fatal
          -Wno-UNUSEDSIGNAL
          --cc
-         --trace
+         {trace_flag}
          -O3
          --build
          --threads 4
@@ -600,7 +724,14 @@ fn generate_cpp_header(
     class_name: &str,
     factory_fn: &str,
     num_uarts: usize,
+    trace_fst: bool,
 ) -> String {
+    let (trace_header, trace_class, trace) = if trace_fst {
+        ("verilated_fst_c.h", "VerilatedFstC", "fst")
+    } else {
+        ("verilated_vcd_c.h", "VerilatedVcdC", "vcd")
+    };
+
     let mut uart_accessors = String::new();
     for i in 0..num_uarts {
         uart_accessors.push_str(&format!(
@@ -622,7 +753,7 @@ fn generate_cpp_header(
 #include "rust/cxx.h"
 
 #include "verilated.h"
-#include "verilated_vcd_c.h"
+#include "{trace_header}"
 
 #include "{model_identifier}.h"
 
@@ -645,34 +776,34 @@ public:
     }}
 
     ~{class_name}() {{
-        close_vcd();
+        close_{trace}();
         if (model_) {{
             model_->final();
         }}
     }}
 
-    void open_vcd(rust::Str path) {{
-        if (vcd_) {{
-            vcd_->close();
+    void open_{trace}(rust::Str path) {{
+        if (trace_) {{
+            trace_->close();
         }}
 
-        if (!vcd_) {{
-            vcd_ = std::make_unique<VerilatedVcdC>();
-            model_->trace(vcd_.get(), 99);
+        if (!trace_) {{
+            trace_ = std::make_unique<{trace_class}>();
+            model_->trace(trace_.get(), 99);
         }}
 
-        vcd_->open(std::string(path).c_str());
+        trace_->open(std::string(path).c_str());
     }}
 
-    void dump_vcd(uint64_t timestamp) {{
-        if (vcd_) {{
-            vcd_->dump(timestamp);
+    void dump_{trace}(uint64_t timestamp) {{
+        if (trace_) {{
+            trace_->dump(timestamp);
         }}
     }}
 
-    void close_vcd() {{
-        if (vcd_) {{
-            vcd_->close();
+    void close_{trace}() {{
+        if (trace_) {{
+            trace_->close();
         }}
     }}
 
@@ -746,10 +877,16 @@ public:
 
     uint8_t get_debug_halted() const {{ return model_->io_debug_halted; }}
 
+    uint8_t get_debug_retired_valid() const {{ return model_->io_debug_retired_valid; }}
+    uint32_t get_debug_retired_bits() const {{ return model_->io_debug_retired_bits; }}
+
+    uint32_t get_debug_irq() const {{ return model_->io_debug_irq; }}
+    void set_debug_irq(uint32_t value) {{ model_->io_debug_irq = value; }}
+
 {uart_accessors}private:
     std::unique_ptr<VerilatedContext> context_;
     std::unique_ptr<::{model_identifier}> model_;
-    std::unique_ptr<VerilatedVcdC> vcd_;
+    std::unique_ptr<{trace_class}> trace_;
 }};
 
 inline std::unique_ptr<{class_name}> {factory_fn}() {{
@@ -18,11 +18,18 @@ fn main() -> Result<()> {
     println!("cargo:rerun-if-changed=build.rs");
     println!("cargo:rerun-if-changed=../../configs/");
     println!("cargo:rerun-if-changed=../../src/main/");
+    println!("cargo:rerun-if-env-changed={}", simtools::TRACE_FST_ENV);
+    if simtools::trace_fst_enabled() {
+        // VerilatedFstC compresses with zlib
+        println!("cargo:rustc-link-lib=z");
+    }
 
     let pattern = workspace_root.join("configs/*.yaml");
     let mut verilator = vec![];
     let mut verilator_monitored = vec![];
     let mut model_names = Vec::new();
+    let mut model_isas = Vec::new();
+    let mut model_memory_regions = Vec::new();
     let mut verilator_constructors = Vec::new();
     let mut verilator_monitored_constructors = Vec::new();
     let mut include_paths = Vec::new();
@@ -35,6 +42,8 @@ fn main() -> Result<()> {
             model_name,
             model_identifier,
             wrapper_name,
+            isa,
+            memory_regions,
             rust,
             verilator_output,
         } = model_info;
@@ -56,6 +65,11 @@ fn main() -> Result<()> {
         verilator.push(rust);
         let model_name_lit = LitStr::new(&model_name, Span::call_site());
         model_names.push(model_name_lit.clone());
+        let isa_lit = LitStr::new(&isa, Span::call_site());
+        model_isas.push(quote! { #model_name_lit => Some(#isa_lit), });
+        model_memory_regions.push(quote! {
+            #model_name_lit => Some(&[#(#memory_regions),*]),
+        });
         let wrapper_ident = format_ident!("{}", wrapper_name);
         verilator_constructors.push(quote! {
             #model_name_lit => Some(std::rc::Rc::new(std::cell::RefCell::new(
@@ -107,6 +121,22 @@ fn main() -> Result<()> {
 
         pub const VERILATOR_MODELS: &[&str] = &[#(#model_names),*];
 
+        pub fn verilator_model_isa(model_name: &str) -> Option<&'static str> {
+            match model_name {
+                #(#model_isas)*
+                _ => None,
+            }
+        }
+
+        pub fn verilator_model_memory_regions(
+            model_name: &str,
+        ) -> Option<&'static [crate::core::MemoryRegion]> {
+            match model_name {
+                #(#model_memory_regions)*
+                _ => None,
+            }
+        }
+
         pub fn create_verilator(
             model_name: &str,
         ) -> Option<std::rc::Rc<std::cell::RefCell<dyn crate::core::SimulatorImpl>>> {
@@ -1,15 +1,217 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::rc::Rc;
-use std::{cell::RefCell, convert::TryInto, path::Path};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use std::{cell::RefCell, convert::TryInto, fs::File};
 
 use anyhow::{Context, Result};
-use elf::abi::{SHF_ALLOC, SHT_NOBITS};
+use elf::abi::{SHF_ALLOC, SHF_EXECINSTR, SHF_WRITE, SHT_NOBITS};
 use elf::{ElfBytes, endian::AnyEndian};
 
-use crate::uart::UartDecoder;
-use crate::{RegisterFile, TestResult};
+use crate::clock::ClockDomain;
+use crate::debug_log::{DebugCommand, DebugLog, DebugTxn};
+use crate::hex::{self, HexFormat};
+use crate::isa::{self, IsaFeatures};
+use crate::stepper::Stepper;
+use crate::uart::{UartDecoder, UartEncoder};
+use crate::{RegisterFile, StopReason, TestResult};
+
+/// Default RTC clock divider - rtcClock runs 50x slower than main clock
+const DEFAULT_RTC_DIVIDER: u64 = 50;
+
+/// Index of the RTC clock in the simulator's clock domains
+const RTC_DOMAIN: usize = 1;
+
+/// Cycles to wait for the debug memory port to accept a request
+const MEM_READY_TIMEOUT: usize = 1000;
+
+/// Cycles to wait for a debug memory request to complete
+const MEM_RESPONSE_TIMEOUT: usize = 30;
+
+/// Cycles run after the core halts before its registers are read
+const DEFAULT_SETTLE_CYCLES: usize = 5;
+
+/// Cycles [`Simulator::step`] waits for the stepped instruction to retire
+const STEP_TIMEOUT: usize = 1000;
+
+/// Breakpoint address no instruction can retire from (PCs are always aligned)
+const BREAKPOINT_DISABLED_PC: u32 = 0xffff_ffff;
+
+/// Why a simulation loop stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HaltReason {
+    /// The core asserted debug halt (breakpoint or watchpoint hit)
+    Halted,
+    /// The cycle budget ran out before the core halted
+    MaxCyclesReached,
+    /// The retired PC stopped advancing (see [`StallDetection`])
+    Stalled { pc: u32 },
+    /// The stop flag was raised (see [`Simulator::set_stop_flag`])
+    Interrupted,
+}
+
+/// What [`Simulator::load_binary`] found in the ELF
+#[derive(Debug, Clone, Default)]
+pub struct LoadedBinary {
+    /// Resolved watchpoint symbols as `(name, address)`, in request order
+    pub watchpoints: Vec<(String, u32)>,
+    /// Total size in bytes of the executable sections that were loaded
+    pub text_size: u64,
+    /// ELF entry point (`e_entry`)
+    pub entry_point: u32,
+}
+
+impl From<HaltReason> for StopReason {
+    fn from(reason: HaltReason) -> Self {
+        match reason {
+            // Nothing but the watchpoint is armed during a plain run
+            HaltReason::Halted => StopReason::Watchpoint,
+            HaltReason::MaxCyclesReached => StopReason::Timeout,
+            HaltReason::Stalled { .. } => StopReason::Stalled,
+            HaltReason::Interrupted => StopReason::Interrupted,
+        }
+    }
+}
+
+/// Where an ELF section would be placed in memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionPlacement {
+    pub name: String,
+    pub addr: u32,
+    pub size: u64,
+    /// Section has no file contents and is zeroed on load (`.bss`)
+    pub zero_fill: bool,
+    /// Section is written at run time (`SHF_WRITE`)
+    pub writable: bool,
+}
+
+/// Result of [`Simulator::validate_binary`]
+#[derive(Debug, Clone)]
+pub struct LoadReport {
+    /// Allocatable sections in ELF header order
+    pub sections: Vec<SectionPlacement>,
+    /// Resolved watchpoint symbols as `(name, address)`, in request order
+    pub watchpoints: Vec<(String, u32)>,
+    /// ELF entry point (`e_entry`)
+    pub entry_point: u32,
+}
+
+/// Address range backed by memory in the simulated SoC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub base: u64,
+    pub length: u64,
+    /// Software must not write here, writable sections are rejected
+    pub readonly: bool,
+    /// Code may run from here
+    pub executable: bool,
+}
+
+impl MemoryRegion {
+    fn contains(&self, section: &SectionPlacement) -> bool {
+        let start = section.addr as u64;
+        start >= self.base && start + section.size <= self.base + self.length
+    }
+
+    fn contains_addr(&self, addr: u32) -> bool {
+        (self.base..self.base + self.length).contains(&(addr as u64))
+    }
+}
+
+/// Early exit for cores stuck in a tight loop such as `j .`
+///
+/// The retired PC is sampled every `sample_interval` cycles; once `threshold`
+/// consecutive samples see the same PC the run stops with
+/// [`HaltReason::Stalled`]. A longer loop whose period divides the sample
+/// interval also looks stalled, so keep the interval odd or the threshold
+/// generous.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StallDetection {
+    pub sample_interval: usize,
+    pub threshold: usize,
+}
+
+/// Waveform format of a trace file, chosen from its extension
+///
+/// Verilator builds one trace format at a time: VCD by default, FST with
+/// `SVAROG_TRACE_FST=1` at build time. Traces in the model's own format are
+/// written directly; VCD variants are dumped next to the target and converted
+/// once the run finishes, which needs the tool named on each variant. FST
+/// traces are only available from FST builds, a run asking for one on a VCD
+/// build fails before it starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// Plain VCD, converted with GTKWave's `fst2vcd` on FST builds
+    Vcd,
+    /// Gzip compressed VCD (`.gz`), compressed with the `gzip` binary
+    VcdGz,
+    /// FST (`.fst`), needs a model built with `SVAROG_TRACE_FST=1`
+    Fst,
+}
+
+impl TraceFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => TraceFormat::VcdGz,
+            Some("fst") => TraceFormat::Fst,
+            _ => TraceFormat::Vcd,
+        }
+    }
+
+    /// Path the C++ side dumps to for a trace ending up at `path`
+    ///
+    /// `native_fst` tells whether the model traces FST rather than VCD.
+    fn raw_path(self, path: &Path, native_fst: bool) -> Result<PathBuf> {
+        Ok(match (self, native_fst) {
+            (TraceFormat::Vcd, false) | (TraceFormat::Fst, true) => path.to_path_buf(),
+            (TraceFormat::VcdGz, false) => path.with_extension(""),
+            (TraceFormat::Fst, false) => anyhow::bail!(
+                "Cannot trace to {:?}: FST needs a model built with SVAROG_TRACE_FST=1",
+                path
+            ),
+            (TraceFormat::Vcd | TraceFormat::VcdGz, true) => path.with_extension("vcd.fst"),
+        })
+    }
+
+    /// Turn the trace dumped to `raw` into `dest` and remove `raw`
+    fn convert(self, raw: &Path, dest: &Path, native_fst: bool) -> Result<()> {
+        let status = match (self, native_fst) {
+            (TraceFormat::Vcd, false) | (TraceFormat::Fst, true) => return Ok(()),
+            (TraceFormat::VcdGz, false) => {
+                let output =
+                    File::create(dest).with_context(|| format!("Failed to create {:?}", dest))?;
+                Command::new("gzip")
+                    .arg("-c")
+                    .arg(raw)
+                    .stdout(output)
+                    .status()
+                    .context("Failed to run gzip, is it on PATH?")?
+            }
+            (TraceFormat::Fst, false) => unreachable!("raw_path rejects FST on VCD builds"),
+            (TraceFormat::Vcd, true) => Command::new("fst2vcd")
+                .arg("-f")
+                .arg(raw)
+                .arg("-o")
+                .arg(dest)
+                .status()
+                .context("Failed to run fst2vcd, is GTKWave installed?")?,
+            (TraceFormat::VcdGz, true) => {
+                // FST to VCD first, then compress that like a VCD build would
+                let vcd = dest.with_extension("");
+                TraceFormat::Vcd.convert(raw, &vcd, true)?;
+                return TraceFormat::VcdGz.convert(&vcd, dest, false);
+            }
+        };
+        if !status.success() {
+            anyhow::bail!("Failed to convert {:?} to {:?}: {}", raw, dest, status);
+        }
 
-/// RTC clock divider - rtcClock runs 50x slower than main clock
-const RTC_CLOCK_DIVIDER: u64 = 50;
+        std::fs::remove_file(raw).with_context(|| format!("Failed to remove {:?}", raw))
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Backend {
@@ -39,12 +241,23 @@ pub(crate) trait SimulatorImpl {
     fn xlen(&self) -> u8;
     fn isa(&self) -> &'static str;
     fn name(&self) -> &'static str;
+    /// Cycles per serial bit of the given UART, if the config specifies it
+    fn uart_bit_period(&self, index: usize) -> Option<u32>;
+    /// Address the cores start from after reset
+    fn reset_vector(&self) -> u32;
+    /// Memories described by the model's config
+    fn memory_regions(&self) -> &'static [MemoryRegion];
 
     fn eval(&self);
     fn final_eval(&self);
     fn open_vcd(&self, path: &str);
     fn dump_vcd(&self, timestamp: u64);
     fn close_vcd(&self);
+    /// Whether the model was built to trace FST instead of VCD
+    fn has_fst(&self) -> bool;
+    fn open_fst(&self, path: &str);
+    fn dump_fst(&self, timestamp: u64);
+    fn close_fst(&self);
 
     fn get_clock(&self) -> u8;
     fn set_clock(&self, value: u8);
@@ -108,6 +321,13 @@ pub(crate) trait SimulatorImpl {
 
     fn get_debug_halted(&self) -> u8;
 
+    fn get_debug_retired_valid(&self) -> u8;
+    fn get_debug_retired_bits(&self) -> u64;
+
+    /// External interrupt lines, bit `i` is the MEIP input of hart `i`
+    fn get_debug_irq(&self) -> u32;
+    fn set_debug_irq(&self, value: u32);
+
     fn get_uart_0_txd(&self) -> u8;
     fn set_uart_0_rxd(&self, value: u8);
     fn get_uart_1_txd(&self) -> u8;
@@ -118,12 +338,44 @@ pub(crate) trait SimulatorImpl {
     }
 }
 
+/// Stall detection state carried between the cycles of a run
+#[derive(Debug, Default)]
+pub(crate) struct LoopState {
+    last_pc: Option<u32>,
+    same_pc_samples: usize,
+    /// The core has left halt since the run was released
+    running: bool,
+}
+
+/// Monitored UART, its decoder and where decoded bytes go
+struct UartConsole {
+    index: usize,
+    decoder: UartDecoder,
+    sink: Box<dyn Write>,
+}
+
 pub struct Simulator {
     model: Rc<RefCell<dyn SimulatorImpl>>,
     timestamp: RefCell<u64>,
     vcd_open: RefCell<bool>,
-    uart_decoder: RefCell<Option<(usize, UartDecoder)>>, // (uart_index, decoder)
-    rtc_counter: RefCell<u64>,                           // Counter for RTC clock division
+    uart_console: RefCell<Option<UartConsole>>,
+    /// RX line encoders of UART 0 and 1, created when input is first queued
+    uart_inputs: RefCell<[Option<UartEncoder>; 2]>,
+    clock_domains: RefCell<Vec<ClockDomain>>,
+    stall_detection: RefCell<Option<StallDetection>>,
+    hart: RefCell<u8>,
+    entry_point: RefCell<u32>,
+    instructions_retired: RefCell<u64>,
+    stop_flag: RefCell<Option<Arc<AtomicBool>>>,
+    reset_before_run: RefCell<bool>,
+    settle_cycles: RefCell<usize>,
+    /// Code addresses of watchpoint symbols past the first, matched against
+    /// the retired PC since the core has a single hardware watchpoint
+    pc_watchpoints: RefCell<Vec<u32>>,
+    /// Where the halted selected hart continues, when known
+    resume_pc: RefCell<Option<u32>>,
+    init_time: Duration,
+    debug_log: RefCell<Option<DebugLog>>,
 }
 
 impl Simulator {
@@ -152,32 +404,288 @@ impl Simulator {
 
         model.set_debug_mem_res_ready(1); // Always ready to receive results
         model.set_debug_reg_res_ready(0); // Not ready until explicitly set
+
+        // UART RX lines idle high
+        model.set_uart_0_rxd(1);
+        model.set_uart_1_rxd(1);
     }
 
     pub fn new(backend: Backend, model_name: &str) -> Result<Self> {
+        let start = Instant::now();
         let model = create_model(backend, model_name)?;
 
         Self::init_debug_interface(&*model.borrow());
+        let reset_vector = model.borrow().reset_vector();
 
         Ok(Simulator {
             model,
             timestamp: RefCell::new(0),
             vcd_open: RefCell::new(false),
-            uart_decoder: RefCell::new(None),
-            rtc_counter: RefCell::new(0),
+            uart_console: RefCell::new(None),
+            uart_inputs: RefCell::new([None, None]),
+            clock_domains: RefCell::new(default_clock_domains()),
+            stall_detection: RefCell::new(None),
+            hart: RefCell::new(0),
+            entry_point: RefCell::new(reset_vector),
+            instructions_retired: RefCell::new(0),
+            stop_flag: RefCell::new(None),
+            reset_before_run: RefCell::new(true),
+            settle_cycles: RefCell::new(DEFAULT_SETTLE_CYCLES),
+            pc_watchpoints: RefCell::new(Vec::new()),
+            resume_pc: RefCell::new(None),
+            init_time: start.elapsed(),
+            debug_log: RefCell::new(None),
         })
     }
 
+    /// Name of the model this simulator was created with
+    pub fn model_name(&self) -> &'static str {
+        self.model.borrow().name()
+    }
+
+    /// XLEN of the simulated core
+    pub fn xlen(&self) -> u8 {
+        self.model.borrow().xlen()
+    }
+
+    /// ISA string of the simulated core, e.g. `rv32i_zicsr`
+    pub fn isa(&self) -> &'static str {
+        self.model.borrow().isa()
+    }
+
+    /// Address the cores start from after reset, from the model's config
+    pub fn reset_vector(&self) -> u32 {
+        self.model.borrow().reset_vector()
+    }
+
+    /// Optional ISA features the simulated core implements
+    pub fn isa_features(&self) -> IsaFeatures {
+        IsaFeatures::from_isa(self.isa())
+    }
+
+    /// Time [`Self::new`] spent constructing and initialising the model
+    pub fn init_time(&self) -> Duration {
+        self.init_time
+    }
+
+    /// Memories of the simulated SoC, as described by the model's config
+    pub fn memory_regions(&self) -> &'static [MemoryRegion] {
+        self.model.borrow().memory_regions()
+    }
+
+    /// PC of the instruction the selected hart retired in the last cycle
+    pub fn retired_pc(&self) -> Option<u32> {
+        let model = self.model.borrow();
+        (model.get_debug_retired_valid() != 0)
+            .then(|| model.mask_to_u32(model.get_debug_retired_bits()))
+    }
+
+    /// Whether a trace is currently being written
+    pub fn is_tracing(&self) -> bool {
+        *self.vcd_open.borrow()
+    }
+
+    /// Stop runs early when the PC stops advancing, `None` (the default)
+    /// disables the check
+    pub fn set_stall_detection(&self, detection: Option<StallDetection>) {
+        *self.stall_detection.borrow_mut() = detection;
+    }
+
+    /// Flag that stops runs early when set, e.g. from a Ctrl-C handler
+    ///
+    /// It is checked every cycle. A run that sees it set stops with
+    /// [`HaltReason::Interrupted`] and still captures registers and closes its
+    /// VCD. The flag is not cleared by the simulator.
+    pub fn set_stop_flag(&self, flag: Option<Arc<AtomicBool>>) {
+        *self.stop_flag.borrow_mut() = flag;
+    }
+
+    /// Whether runs toggle reset before starting the core (the default)
+    ///
+    /// With reset enabled a run asserts reset for two cycles, dumping them to
+    /// the VCD, so the trace starts from the reset state. That also resets any
+    /// reset-sensitive memory, wiping what was loaded. Disable it when the
+    /// core was already reset by a load and memory must be kept as loaded; the
+    /// run then only redirects the halted core to the entry point.
+    pub fn set_reset_before_run(&self, enabled: bool) {
+        *self.reset_before_run.borrow_mut() = enabled;
+    }
+
+    /// Start logging every debug port transaction
+    ///
+    /// Halt/resume, setPC, breakpoints, watchpoints, register reads and memory
+    /// accesses are logged with the time step they were issued at. Without a
+    /// `sink` they are recorded for [`Self::take_debug_log`]; with one each is
+    /// written to it as a line as soon as it is issued, so the log is there
+    /// even when a handshake hangs. Memory loads log every byte, so expect
+    /// large logs when loading binaries. Restarting clears the log.
+    pub fn enable_debug_log(&self, sink: Option<Box<dyn Write>>) {
+        *self.debug_log.borrow_mut() = Some(match sink {
+            Some(sink) => DebugLog::Streamed(sink),
+            None => DebugLog::Recorded(Vec::new()),
+        });
+    }
+
+    /// Transactions recorded since the last call, empty if logging is disabled
+    /// or streams to a sink
+    pub fn take_debug_log(&self) -> Vec<DebugTxn> {
+        self.debug_log
+            .borrow_mut()
+            .as_mut()
+            .map(DebugLog::take)
+            .unwrap_or_default()
+    }
+
+    /// Cycles to keep simulating after the core halts, before registers are
+    /// captured (default 5)
+    ///
+    /// Halt stops fetch, but instructions already in the pipeline still have
+    /// to reach writeback. The count has to cover at least the stages between
+    /// the one that raises halt and writeback, so deeper pipelines or a slow
+    /// writeback need more.
+    pub fn set_settle_cycles(&self, cycles: usize) {
+        *self.settle_cycles.borrow_mut() = cycles;
+    }
+
+    /// Toggle the RTC clock every `divider` main clock cycles
+    ///
+    /// One RTC period is therefore `2 * divider` main clock cycles. Defaults
+    /// to 50. A divider of 0 stops the RTC clock, which freezes
+    /// `mtime`.
+    pub fn set_rtc_divider(&self, divider: u64) {
+        self.clock_domains.borrow_mut()[RTC_DOMAIN].half_period = 2 * divider;
+    }
+
+    /// Select the hart targeted by all following debug commands
+    ///
+    /// Loading, running, breakpoints and register capture all go to this hart,
+    /// and the halted status is reported for it. Defaults to hart 0.
+    pub fn select_hart(&self, id: u8) {
+        *self.hart.borrow_mut() = id;
+        *self.resume_pc.borrow_mut() = None;
+    }
+
+    /// Hart id last driven into the debug port's hart inputs
+    ///
+    /// After a command to another hart this is the selected hart again, see
+    /// [`Self::halt_hart`].
+    pub fn addressed_hart(&self) -> u8 {
+        self.model.borrow().get_debug_hart_in_id_bits()
+    }
+
+    /// Finish the simulation and flush end-of-run model state
+    ///
+    /// Closes an open VCD, flushes the debug log sink and runs the model's
+    /// final evaluation, which is where coverage and assertion results get
+    /// written out. The model must not be ticked again afterwards.
+    pub fn finalize(&self) {
+        if *self.vcd_open.borrow() {
+            self.close_trace();
+        }
+        if let Some(log) = &mut *self.debug_log.borrow_mut() {
+            log.flush();
+        }
+
+        self.model.borrow().final_eval();
+    }
+
     /// Enable UART console monitoring
     ///
     /// When enabled, the simulator will decode UART TX output from the specified
-    /// UART index and print it as ASCII characters during simulation.
+    /// UART index and print it as ASCII characters during simulation. The bit
+    /// period comes from the model's `clockHz` and UART `baud` config; when
+    /// those are missing it is detected from the first byte.
     ///
     /// # Arguments
     /// * `uart_index` - Which UART to monitor (0 or 1)
     pub fn enable_uart_console(&self, uart_index: usize) {
-        *self.uart_decoder.borrow_mut() = Some((uart_index, UartDecoder::new()));
-        eprintln!("UART console monitoring enabled for UART {}", uart_index);
+        self.enable_uart_console_to(uart_index, Box::new(std::io::stdout()));
+    }
+
+    /// Enable UART console monitoring with decoded bytes written to `sink`
+    ///
+    /// Same as [`Self::enable_uart_console`], for capturing the console into a
+    /// file or buffer instead of stdout. The sink is flushed after every byte.
+    pub fn enable_uart_console_to(&self, uart_index: usize, sink: Box<dyn Write>) {
+        let decoder = match self.model.borrow().uart_bit_period(uart_index) {
+            Some(bit_period) => {
+                eprintln!(
+                    "UART console monitoring enabled for UART {} ({} cycles per bit)",
+                    uart_index, bit_period
+                );
+                UartDecoder::with_bit_period(bit_period)
+            }
+            None => {
+                eprintln!(
+                    "UART console monitoring enabled for UART {} (detecting bit period)",
+                    uart_index
+                );
+                UartDecoder::auto()
+            }
+        };
+        *self.uart_console.borrow_mut() = Some(UartConsole {
+            index: uart_index,
+            decoder,
+            sink,
+        });
+    }
+
+    /// Bit period in cycles used by the UART console decoder
+    ///
+    /// `None` when the console is disabled or the period is still being
+    /// detected from the first byte.
+    pub fn uart_console_bit_period(&self) -> Option<u32> {
+        self.uart_console
+            .borrow()
+            .as_ref()
+            .and_then(|console| console.decoder.bit_period())
+    }
+
+    /// Send `bytes` to the RX line of UART `uart_index` (0 or 1)
+    ///
+    /// Bytes are framed like the TX side expects (start bit, 8 data bits, stop
+    /// bit) and shifted out one bit period at a time while the simulation
+    /// ticks, right after anything queued earlier. The bit period comes from
+    /// the model's `clockHz` and UART `baud` config, or from the console
+    /// decoder once it has detected it on the same UART.
+    pub fn queue_uart_input(&self, uart_index: usize, bytes: &[u8]) -> Result<()> {
+        let mut inputs = self.uart_inputs.borrow_mut();
+        let Some(input) = inputs.get_mut(uart_index) else {
+            anyhow::bail!("No UART {}, only UART 0 and 1 can take input", uart_index);
+        };
+
+        if input.is_none() {
+            let console_period = self
+                .uart_console
+                .borrow()
+                .as_ref()
+                .filter(|console| console.index == uart_index)
+                .and_then(|console| console.decoder.bit_period());
+            let bit_period = self
+                .model
+                .borrow()
+                .uart_bit_period(uart_index)
+                .or(console_period)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Bit period of UART {} is unknown, set clockHz and baud in the config",
+                        uart_index
+                    )
+                })?;
+            *input = Some(UartEncoder::with_bit_period(bit_period));
+        }
+
+        input.as_mut().unwrap().queue(bytes);
+        Ok(())
+    }
+
+    /// Whether all bytes queued with [`Self::queue_uart_input`] have been sent
+    pub fn uart_input_done(&self, uart_index: usize) -> bool {
+        self.uart_inputs
+            .borrow()
+            .get(uart_index)
+            .and_then(Option::as_ref)
+            .is_none_or(UartEncoder::is_idle)
     }
 
     /// Load a raw binary file at a specific address
@@ -197,6 +705,54 @@ impl Simulator {
             load_addr
         );
 
+        self.reset_halted(watchpoint_addr);
+
+        // Load binary data to memory
+        self.write_mem(load_addr, &file_data)?;
+
+        // Return entry point (use load_addr if not specified)
+        let entry_point = entry_point.unwrap_or(load_addr);
+        *self.entry_point.borrow_mut() = entry_point;
+        Ok(entry_point)
+    }
+
+    /// Load a Verilog or Intel HEX memory image
+    ///
+    /// Every record is written at its decoded address, so sparse images work.
+    /// Returns the start address record of an Intel HEX file, otherwise the
+    /// address of the first data record.
+    pub fn load_hex<P: AsRef<Path>>(&self, path: P, format: HexFormat) -> Result<u32> {
+        let text = std::fs::read_to_string(path.as_ref()).context("Failed to read hex file")?;
+        let image = hex::parse(&text, format)
+            .with_context(|| format!("Failed to parse {}", path.as_ref().display()))?;
+
+        let Some(first_addr) = image.segments.first().map(|(addr, _)| *addr) else {
+            anyhow::bail!("{} contains no data records", path.as_ref().display());
+        };
+
+        self.reset_halted(None);
+
+        for (addr, data) in &image.segments {
+            eprintln!(
+                "Loading hex segment ({} bytes) at address 0x{:08x}",
+                data.len(),
+                addr
+            );
+            self.write_mem(*addr, data)?;
+        }
+
+        let entry_point = image.entry.unwrap_or(first_addr);
+        *self.entry_point.borrow_mut() = entry_point;
+        Ok(entry_point)
+    }
+
+    /// Put the core into reset with halt asserted, then release reset
+    ///
+    /// Leaves the core halted and ready for memory uploads.
+    fn reset_halted(&self, watchpoint_addr: Option<u32>) {
+        self.pc_watchpoints.borrow_mut().clear();
+        *self.resume_pc.borrow_mut() = None;
+
         // Reset and initialize
         self.model.borrow().set_clock(0);
         self.model.borrow().set_reset(1);
@@ -204,12 +760,17 @@ impl Simulator {
 
         // Set halt
         self.model.borrow().set_debug_hart_in_id_valid(1);
-        self.model.borrow().set_debug_hart_in_id_bits(0);
+        self.model
+            .borrow()
+            .set_debug_hart_in_id_bits(*self.hart.borrow());
         self.model.borrow().set_debug_hart_in_bits_halt_valid(1);
         self.model.borrow().set_debug_hart_in_bits_halt_bits(1);
 
+        self.log_debug(self.now(), DebugCommand::Halt);
+
         // Set watchpoint if provided
         if let Some(addr) = watchpoint_addr {
+            self.log_debug(self.now(), DebugCommand::Watchpoint { addr });
             self.model
                 .borrow()
                 .set_debug_hart_in_bits_watchpoint_valid(1);
@@ -229,114 +790,193 @@ impl Simulator {
         // Take reset low
         self.model.borrow().set_reset(0);
         self.tick(false);
-
-        // Load binary data to memory
-        self.upload_raw_binary(&file_data, load_addr);
-
-        // Return entry point (use load_addr if not specified)
-        Ok(entry_point.unwrap_or(load_addr))
     }
 
+    /// Load an ELF binary into memory
+    ///
+    /// Every name in `watchpoint_symbols` is looked up in the ELF symbol table;
+    /// names that are not present are skipped with a warning. The first
+    /// resolved symbol (in the order given) arms the hardware watchpoint. The
+    /// core has only one, so the remaining symbols stop the run when an
+    /// instruction at their address retires. That only works for symbols in
+    /// executable sections (`_exit`, a trap vector); any other is skipped with
+    /// a warning. The resolved `(name, address)` pairs are returned in the
+    /// same order as part of the [`LoadedBinary`].
     pub fn load_binary<P: AsRef<Path>>(
         &self,
         path: P,
-        watchpoint_symbol: Option<&str>,
-    ) -> anyhow::Result<Option<u32>> {
+        watchpoint_symbols: &[&str],
+    ) -> anyhow::Result<LoadedBinary> {
         let file_data = std::fs::read(path)?;
         let slice = file_data.as_slice();
         let file = ElfBytes::<AnyEndian>::minimal_parse(slice)?;
 
-        // Resolve watchpoint symbol address if provided
-        let watchpoint_addr = if let Some(symbol_name) = watchpoint_symbol {
-            if let Some(symtab) = file.symbol_table()? {
-                let mut found_addr = None;
-                for symbol in symtab.0.iter() {
-                    if let Ok(name) = symtab.1.get(symbol.st_name as usize) {
-                        if name == symbol_name {
-                            found_addr = Some(symbol.st_value as u32);
-                            eprintln!(
-                                "Found symbol '{}' at address 0x{:08x}",
-                                symbol_name, symbol.st_value
-                            );
-                            break;
-                        }
-                    }
-                }
-                found_addr
-            } else {
-                eprintln!("Warning: No symbol table found in ELF file");
-                None
+        let watchpoints = resolve_symbols(&file, watchpoint_symbols)?;
+        let sections = loadable_sections(&file)?;
+
+        let mut pc_watchpoints = Vec::new();
+        for (name, addr) in watchpoints.iter().skip(1) {
+            let in_code = sections.iter().any(|section| {
+                let placement = &section.placement;
+                let start = placement.addr as u64;
+                section.executable && (start..start + placement.size).contains(&(*addr as u64))
+            });
+            if !in_code {
+                eprintln!(
+                    "Warning: only one hardware watchpoint available, skipping '{}' (0x{:08x}): not a code address",
+                    name, addr
+                );
+                continue;
             }
-        } else {
-            None
-        };
+            pc_watchpoints.push((name, *addr));
+        }
 
-        // IMPORTANT: Reset FIRST before loading memory!
-        // Memory uses RegInit, so reset clears it to all zeros.
-        // We must reset first, then load memory after.
+        // Reset first: memory uses RegInit, so reset clears it to all zeros
+        self.reset_halted(watchpoints.first().map(|(_, addr)| *addr));
+        for (name, addr) in &pc_watchpoints {
+            eprintln!(
+                "Setting PC watchpoint on '{}' at address: 0x{:08x}",
+                name, addr
+            );
+        }
+        *self.pc_watchpoints.borrow_mut() = pc_watchpoints.iter().map(|(_, addr)| *addr).collect();
 
-        // Establish initial state: clock low, then apply reset
-        self.model.borrow().set_clock(0);
-        self.model.borrow().set_reset(1);
+        let mut text_size = 0;
 
-        // Initialize debug interface first, THEN set halt
-        // (init_debug_interface clears all signals including halt)
-        Self::init_debug_interface(&*self.model.borrow());
+        // Load all allocatable sections (including .rodata)
+        for section in sections {
+            let placement = &section.placement;
+
+            // Zero .bss and friends explicitly instead of relying on reset
+            let Some(data) = section.data else {
+                eprintln!(
+                    "Zeroing section {} ({} bytes) starting at address 0x{:08x}",
+                    placement.name, placement.size, placement.addr
+                );
+                self.fill_mem(placement.addr, placement.size as usize, 0)?;
+                continue;
+            };
 
-        // Set halt through debug interface
-        // IMPORTANT: Must set id_valid and id_bits to route commands to hart 0
-        self.model.borrow().set_debug_hart_in_id_valid(1);
-        self.model.borrow().set_debug_hart_in_id_bits(0); // Hart 0
-        self.model.borrow().set_debug_hart_in_bits_halt_valid(1);
-        self.model.borrow().set_debug_hart_in_bits_halt_bits(1);
+            self.upload_section(&placement.name, data, placement.addr)?;
 
-        // Set watchpoint if address was resolved
-        if let Some(addr) = watchpoint_addr {
-            self.model
-                .borrow()
-                .set_debug_hart_in_bits_watchpoint_valid(1);
-            self.model
-                .borrow()
-                .set_debug_hart_in_bits_watchpoint_bits_addr(addr as u64);
-            eprintln!("Setting watchpoint on address: 0x{:08x}", addr);
+            if section.executable {
+                text_size += placement.size;
+            }
         }
 
-        // Evaluate to apply reset before first clock edge
-        self.model.borrow().eval();
+        let entry_point = file.ehdr.e_entry as u32;
+        eprintln!("ELF entry point: 0x{:08x}", entry_point);
+        *self.entry_point.borrow_mut() = entry_point;
 
-        // Reset for a few cycles
-        for _ in 0..5 {
-            self.tick(false);
-        }
+        Ok(LoadedBinary {
+            watchpoints,
+            text_size,
+            entry_point,
+        })
+    }
 
-        // Take reset low before loading sections so the core starts from a clean
-        // slate once we release halt later.
-        self.model.borrow().set_reset(0);
-        self.tick(false);
+    /// Check that an ELF binary would load cleanly, without touching the model
+    ///
+    /// Parses the ELF, resolves `watchpoint_symbols` like [`Self::load_binary`]
+    /// and checks that every allocatable section fits in one of the model's
+    /// memory regions, that no writable section lands in a readonly region and,
+    /// when the config marks any region executable, that the entry point is in
+    /// one of them. No memory writes are issued and the model is not ticked.
+    pub fn validate_binary<P: AsRef<Path>>(
+        &self,
+        path: P,
+        watchpoint_symbols: &[&str],
+    ) -> Result<LoadReport> {
+        let file_data = std::fs::read(path.as_ref())
+            .with_context(|| format!("Failed to read {}", path.as_ref().display()))?;
+        let file = ElfBytes::<AnyEndian>::minimal_parse(file_data.as_slice())?;
+
+        let watchpoints = resolve_symbols(&file, watchpoint_symbols)?;
+        let sections: Vec<SectionPlacement> = loadable_sections(&file)?
+            .into_iter()
+            .map(|section| section.placement)
+            .collect();
+
+        let entry_point = file.ehdr.e_entry as u32;
+        check_placement(self.model.borrow().memory_regions(), &sections, entry_point)?;
+
+        Ok(LoadReport {
+            sections,
+            watchpoints,
+            entry_point,
+        })
+    }
 
-        // Load all allocatable sections (including .rodata)
-        let (shdrs_opt, strtab_opt) = file.section_headers_with_strtab()?;
-        if let (Some(shdrs), Some(strtab)) = (shdrs_opt, strtab_opt) {
-            for shdr in shdrs.iter() {
-                let is_alloc = (shdr.sh_flags & (SHF_ALLOC as u64)) != 0;
-                let is_nobits = shdr.sh_type == (SHT_NOBITS as u32);
-                if !is_alloc || is_nobits || shdr.sh_size == 0 {
-                    continue;
-                }
+    /// Read back the RTL memory covered by an ELF section
+    ///
+    /// Looks up `section_name` among the allocatable sections of the ELF at
+    /// `path` and returns the current memory contents of exactly that range,
+    /// e.g. to compare the final `.data` image against an expected buffer.
+    pub fn dump_section<P: AsRef<Path>>(&self, path: P, section_name: &str) -> Result<Vec<u8>> {
+        let file_data = std::fs::read(path.as_ref())
+            .with_context(|| format!("Failed to read {}", path.as_ref().display()))?;
+        let file = ElfBytes::<AnyEndian>::minimal_parse(file_data.as_slice())?;
+
+        let section = loadable_sections(&file)?
+            .into_iter()
+            .find(|section| section.placement.name == section_name)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "No allocatable section '{}' in {}",
+                    section_name,
+                    path.as_ref().display()
+                )
+            })?;
+
+        self.read_mem(section.placement.addr, section.placement.size as usize)
+    }
 
-                let name = strtab.get(shdr.sh_name as usize).unwrap_or("<unknown>");
-                let (data, _) = file.section_data(&shdr)?;
-                let start_addr = shdr.sh_addr as u32;
-                self.upload_section(name, data, start_addr);
+    /// Fill `len` bytes of memory starting at `addr` with `value`
+    ///
+    /// Goes through the debug memory port, using word writes for the aligned
+    /// part of the range and byte writes at the edges.
+    pub fn fill_mem(&self, addr: u32, len: usize, value: u8) -> Result<()> {
+        let end = addr as u64 + len as u64;
+        let word = u32::from_le_bytes([value; 4]);
+
+        let mut cur = addr as u64;
+        while cur < end {
+            if cur.is_multiple_of(4) && end - cur >= 4 {
+                self.write_mem_word(cur as u32, word)?;
+                cur += 4;
+            } else {
+                self.write_mem_byte(cur as u32, value)?;
+                cur += 1;
             }
-        } else {
-            eprintln!("Warning: No section headers found in ELF file");
         }
 
-        Ok(watchpoint_addr)
+        Ok(())
     }
 
-    fn upload_section(&self, section_name: &str, data: &[u8], start_addr: u32) {
+    /// Write `data` to memory starting at `addr`
+    ///
+    /// Like [`Self::fill_mem`], aligned words go out as word writes and any
+    /// unaligned head or tail as byte writes.
+    pub fn write_mem(&self, addr: u32, data: &[u8]) -> Result<()> {
+        let end = addr as u64 + data.len() as u64;
+
+        let mut cur = addr as u64;
+        while cur < end {
+            let offset = (cur - addr as u64) as usize;
+            if cur.is_multiple_of(4) && end - cur >= 4 {
+                let word = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+                self.write_mem_word(cur as u32, word)?;
+                cur += 4;
+            } else {
+                self.write_mem_byte(cur as u32, data[offset])?;
+                cur += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn upload_section(&self, section_name: &str, data: &[u8], start_addr: u32) -> Result<()> {
         eprintln!(
             "Loading section {} ({} bytes) starting at address 0x{:08x}",
             section_name,
@@ -344,49 +984,13 @@ impl Simulator {
             start_addr
         );
 
-        let mut chunk_iter = data.chunks_exact(4);
-        for (i, chunk) in chunk_iter.by_ref().enumerate() {
-            let word = u32::from_le_bytes(chunk.try_into().unwrap());
-            let addr = start_addr + (i as u32 * 4);
-            if i < 10 {
-                eprintln!("  [0x{:08x}] = 0x{:08x}", addr, word);
-            }
-            self.write_mem_word(addr, word);
-        }
-
-        let remainder = chunk_iter.remainder();
-        if !remainder.is_empty() {
-            let start_offset = (data.len() - remainder.len()) as u32;
-            for (byte_offset, byte) in remainder.iter().enumerate() {
-                let addr = start_addr + start_offset + byte_offset as u32;
-                self.write_mem_byte(addr, *byte);
-            }
-        }
-    }
-
-    fn upload_raw_binary(&self, data: &[u8], start_addr: u32) {
-        let mut chunk_iter = data.chunks_exact(4);
-        for (i, chunk) in chunk_iter.by_ref().enumerate() {
-            let word = u32::from_le_bytes(chunk.try_into().unwrap());
-            let addr = start_addr + (i as u32 * 4);
-            if i < 10 {
-                eprintln!("  [0x{:08x}] = 0x{:08x}", addr, word);
-            }
-            self.write_mem_word(addr, word);
-        }
-
-        let remainder = chunk_iter.remainder();
-        if !remainder.is_empty() {
-            let start_offset = (data.len() - remainder.len()) as u32;
-            for (byte_offset, byte) in remainder.iter().enumerate() {
-                let addr = start_addr + start_offset + byte_offset as u32;
-                self.write_mem_byte(addr, *byte);
-            }
-        }
+        self.write_mem(start_addr, data)
     }
 
+    /// Run from the entry point of the last loaded binary (the model's reset
+    /// vector if nothing was loaded yet)
     pub fn run(&self, vcd_path: Option<&Path>, max_cycles: usize) -> Result<TestResult> {
-        self.run_with_entry_point(vcd_path, max_cycles, 0x80000000)
+        self.run_with_entry_point(vcd_path, max_cycles, *self.entry_point.borrow())
     }
 
     pub fn run_with_entry_point(
@@ -398,174 +1002,643 @@ impl Simulator {
         self.run_with_entry_point_and_progress(vcd_path, max_cycles, entry_point, |_| {})
     }
 
+    /// Run from `entry_point`, calling `on_cycle` with the cycle count as the
+    /// simulation advances
+    ///
+    /// A `vcd_path` in a format the model does not trace directly, such as
+    /// `.gz`, is dumped next to it first and converted into place once the run
+    /// finishes, see [`TraceFormat`]. Compression shells out to `gzip`, which
+    /// must be on `PATH`. An `.fst` path on a model built for VCD fails before
+    /// the run starts.
+    ///
+    /// The run starts with a short reset unless disabled with
+    /// [`Self::set_reset_before_run`], in which case it relies on the reset done
+    /// by the previous load.
     pub fn run_with_entry_point_and_progress<F>(
         &self,
         vcd_path: Option<&Path>,
         max_cycles: usize,
         entry_point: u32,
-        mut on_cycle: F,
+        on_cycle: F,
     ) -> Result<TestResult>
     where
         F: FnMut(usize),
     {
-        if vcd_path.is_some() {
-            self.model
-                .borrow()
-                .open_vcd(vcd_path.unwrap().to_str().unwrap());
-            *self.vcd_open.borrow_mut() = true;
+        let raw_vcd = self.start_run(vcd_path, entry_point)?;
+        let reason = self.run_loop(max_cycles, vcd_path.is_some(), on_cycle);
+        self.finish_run(vcd_path, raw_vcd.as_deref(), reason)
+    }
+
+    /// Start a run from `entry_point` that the caller drives cycle by cycle
+    ///
+    /// Does the same set-up as [`Self::run_with_entry_point_and_progress`] and
+    /// hands back a [`Stepper`]; call [`Stepper::advance`] for every cycle and
+    /// [`Stepper::finish`] to close the VCD and capture registers.
+    pub fn stepper(&self, vcd_path: Option<&Path>, entry_point: u32) -> Result<Stepper<'_>> {
+        let raw_vcd = self.start_run(vcd_path, entry_point)?;
+        Ok(Stepper::new(self, vcd_path.map(Path::to_path_buf), raw_vcd))
+    }
+
+    /// Open the VCD, reset the core and release it at `entry_point`
+    ///
+    /// Returns the path the VCD is actually dumped to.
+    fn start_run(&self, vcd_path: Option<&Path>, entry_point: u32) -> Result<Option<PathBuf>> {
+        let native_fst = self.model.borrow().has_fst();
+        let raw_vcd = vcd_path
+            .map(|path| TraceFormat::from_path(path).raw_path(path, native_fst))
+            .transpose()?;
+        if let Some(raw_vcd) = &raw_vcd {
+            self.open_trace(raw_vcd);
+        }
+
+        if let Err(err) = self.prepare(entry_point) {
+            if raw_vcd.is_some() {
+                self.close_trace();
+            }
+            return Err(err);
         }
 
+        // Release halt to start execution. The run loop ticks from here on so
+        // every instruction it retires is observed and counted.
+        *self.instructions_retired.borrow_mut() = 0;
+        self.release_halt();
+        Ok(raw_vcd)
+    }
+
+    /// Get the loaded program ready to run from `entry`, but keep it halted
+    ///
+    /// Does the reset (see [`Self::set_reset_before_run`]), setPC and flush
+    /// sequence a run starts with, so [`Self::peek_registers`] shows the state
+    /// before the first instruction. A following run repeats this sequence.
+    /// Fails without touching the model if `entry` is not executable memory.
+    pub fn prepare(&self, entry: u32) -> Result<()> {
+        check_entry_point(self.model.borrow().memory_regions(), entry)?;
+
         // Toggle reset while dumping a couple of baseline cycles so the trace captures
         // the CPU at the architectural reset vector before we let the pipeline run.
-        self.model.borrow().set_reset(1);
-        for _ in 0..2 {
+        if *self.reset_before_run.borrow() {
+            self.model.borrow().set_reset(1);
+            for _ in 0..2 {
+                self.tick(true);
+            }
+            self.model.borrow().set_reset(0);
             self.tick(true);
         }
-        self.model.borrow().set_reset(0);
-        self.tick(true);
 
         // Set PC to program entry point and flush pipeline before releasing halt
+        self.set_pc(entry);
+        Ok(())
+    }
+
+    /// Close and convert the VCD of a run and capture its result
+    pub(crate) fn finish_run(
+        &self,
+        vcd_path: Option<&Path>,
+        raw_vcd: Option<&Path>,
+        reason: HaltReason,
+    ) -> Result<TestResult> {
+        if let (Some(vcd_path), Some(raw_vcd)) = (vcd_path, raw_vcd) {
+            self.close_trace();
+            let native_fst = self.model.borrow().has_fst();
+            TraceFormat::from_path(vcd_path).convert(raw_vcd, vcd_path, native_fst)?;
+        }
+
+        let regs = self.capture_registers()?;
+        let exit_code = regs.get(3); // x3/gp holds test result
+
+        Ok(TestResult {
+            regs,
+            exit_code: Some(exit_code),
+            stop_reason: reason.into(),
+            instructions_retired: *self.instructions_retired.borrow(),
+        })
+    }
+
+    /// Halt the core and redirect it to `pc`
+    ///
+    /// Pulses `setPC`, which also flushes the pipeline. The core stays halted
+    /// afterwards, so this can be combined freely with breakpoints and
+    /// [`Self::run_until_pc`].
+    pub fn set_pc(&self, pc: u32) {
+        self.log_debug(self.now(), DebugCommand::SetPc { pc });
         self.model.borrow().set_debug_hart_in_id_valid(1);
-        self.model.borrow().set_debug_hart_in_id_bits(0); // Hart 0
+        self.model
+            .borrow()
+            .set_debug_hart_in_id_bits(*self.hart.borrow());
+        self.model.borrow().set_debug_hart_in_bits_halt_valid(1);
+        self.model.borrow().set_debug_hart_in_bits_halt_bits(1);
         self.model.borrow().set_debug_hart_in_bits_set_pc_valid(1);
         self.model
             .borrow()
-            .set_debug_hart_in_bits_set_pc_bits_pc(entry_point as u64);
-        eprintln!("Setting PC to 0x{:08x} and flushing pipeline", entry_point);
+            .set_debug_hart_in_bits_set_pc_bits_pc(pc as u64);
         self.tick(true);
         self.model.borrow().set_debug_hart_in_bits_set_pc_valid(0);
         self.tick(true);
+        *self.resume_pc.borrow_mut() = Some(pc);
+    }
 
-        // Release halt to start execution
-        self.model.borrow().set_debug_mem_in_valid(0); // Disable memory writes
-        self.model.borrow().set_debug_hart_in_id_valid(1);
-        self.model.borrow().set_debug_hart_in_id_bits(0); // Hart 0
-        self.model.borrow().set_debug_hart_in_bits_halt_valid(1);
-        self.model.borrow().set_debug_hart_in_bits_halt_bits(0); // Release halt
-        eprintln!("CPU halt released, starting execution");
-        self.tick(true);
+    /// Resume execution until the instruction at `target` retires
+    ///
+    /// Arms a breakpoint at `target`, releases halt and runs for at most
+    /// `max_cycles`. Execution continues from wherever the core is currently
+    /// halted. The breakpoint is cleared afterwards and the core is left halted
+    /// so its state can be inspected. A watchpoint hit also stops the run and is
+    /// reported as [`HaltReason::Halted`].
+    pub fn run_until_pc(&self, target: u32, max_cycles: usize) -> Result<HaltReason> {
+        self.set_breakpoint(target);
+        self.release_halt();
+        let reason = self.run_loop(max_cycles, true, |_| {});
+        self.clear_breakpoint();
+        self.halt();
+
+        Ok(reason)
+    }
 
-        // Clear id.valid and halt.valid to enter "don't care" state
-        // This allows internal events (watchpoints, breakpoints) to assert halt
-        self.model.borrow().set_debug_hart_in_id_valid(0);
-        self.model.borrow().set_debug_hart_in_bits_halt_valid(0);
-        eprintln!("Cleared halt.valid to 'don't care' state");
+    /// Execute a single instruction on the halted core
+    ///
+    /// Arms a breakpoint on the instruction the core continues at and releases
+    /// halt, so exactly that instruction retires before the core halts again.
+    /// Returns the PC the core continues at after it, decoded from the
+    /// instruction beforehand. That needs a known starting point: call
+    /// [`Self::set_pc`] (or [`Self::prepare`]) first and again after any other
+    /// way of resuming. Stepping over `ecall`, `ebreak` or `mret` returns
+    /// `None`, their target lives in a CSR, so set the PC again before the
+    /// next step.
+    pub fn step(&self) -> Result<Option<u32>> {
+        let Some(pc) = *self.resume_pc.borrow() else {
+            anyhow::bail!(
+                "step: PC of hart {} is unknown, call set_pc first",
+                *self.hart.borrow()
+            );
+        };
+        let inst = self.read_mem_word(pc)?;
+        let next = isa::next_pc(inst, pc, |idx| self.read_register(idx))?;
+
+        self.set_breakpoint(pc);
+        self.release_halt();
 
-        // Tick more cycles to fully clear pipeline after halt
-        for _ in 0..10 {
+        let mut retired = false;
+        let mut waited = 0;
+        loop {
+            self.count_retired();
+            retired |= self.retired_pc() == Some(pc);
+            if retired && self.model.borrow().get_debug_halted() != 0 {
+                break;
+            }
+            if waited == STEP_TIMEOUT {
+                self.clear_breakpoint();
+                self.halt();
+                anyhow::bail!(
+                    "Instruction at 0x{:08x} did not retire on hart {} within {} cycles",
+                    pc,
+                    *self.hart.borrow(),
+                    STEP_TIMEOUT
+                );
+            }
             self.tick(true);
+            waited += 1;
         }
 
-        // Check if halt was actually released
-        let halted = self.model.borrow().get_debug_halted() != 0;
-        eprintln!("After release+10cycles: halted={}", halted);
+        self.clear_breakpoint();
+        self.halt();
+        *self.resume_pc.borrow_mut() = next;
+
+        Ok(next)
+    }
+
+    /// Halt the core once the instruction at `pc` retires
+    ///
+    /// The debug interface holds a single breakpoint, setting a new one
+    /// replaces the previous address.
+    pub fn set_breakpoint(&self, pc: u32) {
+        self.log_debug(self.now(), DebugCommand::Breakpoint { pc });
+        let id_valid = self.model.borrow().get_debug_hart_in_id_valid();
+        self.model.borrow().set_debug_hart_in_id_valid(1);
+        self.model
+            .borrow()
+            .set_debug_hart_in_id_bits(*self.hart.borrow());
+        self.model
+            .borrow()
+            .set_debug_hart_in_bits_breakpoint_valid(1);
+        self.model
+            .borrow()
+            .set_debug_hart_in_bits_breakpoint_bits_pc(pc as u64);
+        self.tick(true);
+        self.model
+            .borrow()
+            .set_debug_hart_in_bits_breakpoint_valid(0);
+        self.model.borrow().set_debug_hart_in_id_valid(id_valid);
+    }
+
+    /// Disarm the breakpoint
+    ///
+    /// The hardware has no breakpoint enable bit, so the breakpoint is parked
+    /// on an address that can never match.
+    pub fn clear_breakpoint(&self) {
+        self.set_breakpoint(BREAKPOINT_DISABLED_PC);
+    }
+
+    /// Raise external interrupt line `irq`
+    ///
+    /// Line `i` drives the machine external interrupt (MEIP) of hart `i`; the
+    /// level is held across ticks until [`Simulator::clear_interrupt`]. Lines
+    /// without a hart behind them are ignored by the hardware.
+    pub fn assert_interrupt(&self, irq: u32) -> Result<()> {
+        self.drive_interrupt(irq, true)
+    }
+
+    /// Lower external interrupt line `irq`
+    pub fn clear_interrupt(&self, irq: u32) -> Result<()> {
+        self.drive_interrupt(irq, false)
+    }
+
+    fn drive_interrupt(&self, irq: u32, level: bool) -> Result<()> {
+        if irq >= 32 {
+            anyhow::bail!("Interrupt line {} out of range, the debug port has 32", irq);
+        }
+        self.log_debug(self.now(), DebugCommand::Interrupt { irq, level });
+        let model = self.model.borrow();
+        let lines = model.get_debug_irq();
+        let lines = if level {
+            lines | (1 << irq)
+        } else {
+            lines & !(1 << irq)
+        };
+        model.set_debug_irq(lines);
+        Ok(())
+    }
+
+    /// Run until the core halts or `max_cycles` elapse
+    fn run_loop<F>(&self, max_cycles: usize, dump_vcd: bool, mut on_cycle: F) -> HaltReason
+    where
+        F: FnMut(usize),
+    {
+        let mut state = LoopState::default();
 
         for cycle in 0..max_cycles {
-            self.tick(vcd_path.is_some());
-            on_cycle(cycle + 1);
-
-            // Sample UART TX if console monitoring is enabled
-            if let Some((uart_index, decoder)) = &mut *self.uart_decoder.borrow_mut() {
-                let txd = match uart_index {
-                    0 => self.model.borrow().get_uart_0_txd(),
-                    1 => self.model.borrow().get_uart_1_txd(),
-                    _ => 0,
-                };
-
-                if let Some(byte) = decoder.process(txd) {
-                    // Print the decoded byte as ASCII
-                    print!("{}", byte as char);
-                    std::io::Write::flush(&mut std::io::stdout()).ok();
+            match self.run_cycle(cycle, dump_vcd, &mut state) {
+                Some(HaltReason::Interrupted) => return HaltReason::Interrupted,
+                reason => {
+                    on_cycle(cycle + 1);
+                    if let Some(reason) = reason {
+                        return reason;
+                    }
                 }
             }
+        }
 
-            // Check if CPU has halted (watchpoint hit)
-            let halted = self.model.borrow().get_debug_halted() != 0;
+        HaltReason::MaxCyclesReached
+    }
 
-            if halted {
-                eprintln!("\nCPU halted at cycle {}, watchpoint triggered", cycle);
-                // Run a few more cycles to let the pipeline settle
-                for _ in 0..5 {
-                    self.tick(vcd_path.is_some());
-                }
-                break;
+    /// Simulate cycle number `cycle` of a run, `Some` once the run has to stop
+    pub(crate) fn run_cycle(
+        &self,
+        cycle: usize,
+        dump_vcd: bool,
+        state: &mut LoopState,
+    ) -> Option<HaltReason> {
+        if let Some(flag) = &*self.stop_flag.borrow()
+            && flag.load(Ordering::Relaxed)
+        {
+            eprintln!("\nSimulation interrupted at cycle {}", cycle);
+            return Some(HaltReason::Interrupted);
+        }
+
+        self.tick(dump_vcd);
+        self.count_retired();
+
+        // Sample UART TX if console monitoring is enabled
+        if let Some(console) = &mut *self.uart_console.borrow_mut() {
+            let txd = match console.index {
+                0 => self.model.borrow().get_uart_0_txd(),
+                1 => self.model.borrow().get_uart_1_txd(),
+                _ => 0,
+            };
+
+            if let Some(byte) = console.decoder.process(txd) {
+                console.sink.write_all(&[byte]).ok();
+                console.sink.flush().ok();
             }
         }
 
-        if vcd_path.is_some() {
-            self.model.borrow().close_vcd();
-            *self.vcd_open.borrow_mut() = false;
+        // Check if CPU has halted (breakpoint or watchpoint hit). Halt status
+        // lags the release by a few cycles, so only a halt after the core has
+        // been seen running ends the run.
+        let halted = self.model.borrow().get_debug_halted() != 0;
+        if !halted {
+            state.running = true;
         }
 
-        let regs = self.capture_registers()?;
-        let exit_code = regs.get(3); // x3/gp holds test result
+        if halted && state.running {
+            eprintln!("\nCPU halted at cycle {}", cycle);
+            // Run a few more cycles to let the pipeline settle
+            for _ in 0..*self.settle_cycles.borrow() {
+                self.tick(dump_vcd);
+                self.count_retired();
+            }
+            return Some(HaltReason::Halted);
+        }
 
-        Ok(TestResult {
-            regs,
-            exit_code: Some(exit_code),
-        })
+        // Watchpoint symbols without hardware support stop on the retired PC
+        if let Some(pc) = self.retired_pc()
+            && self.pc_watchpoints.borrow().contains(&pc)
+        {
+            eprintln!(
+                "\nCPU reached watchpoint pc=0x{:08x} at cycle {}",
+                pc, cycle
+            );
+            self.halt();
+            for _ in 0..*self.settle_cycles.borrow() {
+                self.tick(dump_vcd);
+                self.count_retired();
+            }
+            return Some(HaltReason::Halted);
+        }
+
+        let detection = (*self.stall_detection.borrow())?;
+        if !(cycle + 1).is_multiple_of(detection.sample_interval.max(1)) {
+            return None;
+        }
+
+        let model = self.model.borrow();
+        if model.get_debug_retired_valid() == 0 {
+            return None;
+        }
+
+        let pc = model.mask_to_u32(model.get_debug_retired_bits());
+        if state.last_pc == Some(pc) {
+            state.same_pc_samples += 1;
+        } else {
+            state.last_pc = Some(pc);
+            state.same_pc_samples = 0;
+        }
+
+        if state.same_pc_samples >= detection.threshold {
+            eprintln!("\nCPU stalled at pc=0x{:08x} at cycle {}", pc, cycle);
+            return Some(HaltReason::Stalled { pc });
+        }
+
+        None
     }
 
-    fn capture_registers(&self) -> Result<RegisterFile> {
-        let mut regs = RegisterFile::new();
+    /// Assert halt on the selected hart
+    fn halt(&self) {
+        self.halt_on(*self.hart.borrow());
+    }
 
-        // Ensure CPU is halted
+    /// Release halt on the selected hart
+    fn release_halt(&self) {
+        self.release_on(*self.hart.borrow());
+    }
+
+    /// Assert halt on `hart`
+    ///
+    /// Does not change the hart selected with [`Self::select_hart`], so other
+    /// cores can be stopped while the selected one keeps running.
+    pub fn halt_hart(&self, hart: u8) {
+        self.on_hart(hart, || self.halt_on(hart));
+    }
+
+    /// Release halt on `hart`, see [`Self::halt_hart`]
+    pub fn resume_hart(&self, hart: u8) {
+        self.on_hart(hart, || self.release_on(hart));
+    }
+
+    /// Run `command` against `hart`, then address the selected hart again
+    ///
+    /// The chip reports halted status, retirement and register results for the
+    /// last addressed hart, so a command to another hart is followed by a tick
+    /// that points them back. The selected hart's halt request is restored.
+    fn on_hart(&self, hart: u8, command: impl FnOnce()) {
+        let selected = *self.hart.borrow();
+        if hart == selected {
+            return command();
+        }
+
+        let halt_valid = self.model.borrow().get_debug_hart_in_bits_halt_valid();
+        let halt_bits = self.model.borrow().get_debug_hart_in_bits_halt_bits();
+        command();
+
+        let model = self.model.borrow();
+        model.set_debug_hart_in_id_valid(1);
+        model.set_debug_hart_in_id_bits(selected);
+        model.set_debug_hart_in_bits_halt_valid(halt_valid);
+        model.set_debug_hart_in_bits_halt_bits(halt_bits);
+        drop(model);
+        self.tick(false);
+    }
+
+    /// Assert halt on `hart` through the debug interface
+    fn halt_on(&self, hart: u8) {
         self.model.borrow().set_debug_hart_in_id_valid(1);
-        self.model.borrow().set_debug_hart_in_id_bits(0); // Hart 0
+        self.model.borrow().set_debug_hart_in_id_bits(hart);
         self.model.borrow().set_debug_hart_in_bits_halt_valid(1);
         self.model.borrow().set_debug_hart_in_bits_halt_bits(1);
+        // Log the id actually driven, not the one asked for
+        self.log_hart_debug(self.addressed_hart(), self.now(), DebugCommand::Halt);
+        self.tick(false);
+    }
+
+    /// Release halt on `hart` and return the hart inputs to the "don't care"
+    /// state
+    ///
+    /// With id.valid and halt.valid low, internal events (watchpoints,
+    /// breakpoints) are free to assert halt again.
+    fn release_on(&self, hart: u8) {
+        if hart == *self.hart.borrow() {
+            *self.resume_pc.borrow_mut() = None;
+        }
+        self.model.borrow().set_debug_mem_in_valid(0); // Disable memory writes
+        self.model.borrow().set_debug_hart_in_id_valid(1);
+        self.model.borrow().set_debug_hart_in_id_bits(hart);
+        self.model.borrow().set_debug_hart_in_bits_halt_valid(1);
+        self.model.borrow().set_debug_hart_in_bits_halt_bits(0); // Release halt
+        self.log_hart_debug(self.addressed_hart(), self.now(), DebugCommand::Resume);
+        self.tick(true);
+
+        self.model.borrow().set_debug_hart_in_id_valid(0);
+        self.model.borrow().set_debug_hart_in_bits_halt_valid(0);
+    }
+
+    /// Read the register file of a core that is already halted
+    ///
+    /// Unlike the capture done at the end of [`Self::run`], this does not
+    /// assert halt, so a core stopped at a breakpoint can be inspected and then
+    /// resumed without extra halt/release churn. Fails if the core is running.
+    pub fn peek_registers(&self) -> Result<RegisterFile> {
+        if self.model.borrow().get_debug_halted() == 0 {
+            anyhow::bail!("peek_registers requires a halted core");
+        }
+
+        let id_valid = self.model.borrow().get_debug_hart_in_id_valid();
+        self.model.borrow().set_debug_reg_res_ready(1); // Ready to receive results
+        let regs = self.read_register_file();
+        self.model.borrow().set_debug_hart_in_id_valid(id_valid);
+
+        regs
+    }
+
+    fn capture_registers(&self) -> Result<RegisterFile> {
+        // Ensure CPU is halted
+        self.model.borrow().set_debug_reg_res_ready(1); // Ready to receive results
+        self.halt();
+
+        self.read_register_file()
+    }
+
+    /// Read register `idx` of the halted core
+    pub fn read_register(&self, idx: u8) -> Result<u32> {
+        self.check_register_access("read_register", idx)?;
+
+        let id_valid = self.model.borrow().get_debug_hart_in_id_valid();
         self.model.borrow().set_debug_reg_res_ready(1); // Ready to receive results
+        self.start_register_request(false, 0);
+        let value = self.request_register(idx);
+        self.model.borrow().set_debug_hart_in_bits_register_valid(0);
+        self.model.borrow().set_debug_hart_in_id_valid(id_valid);
+
+        value
+    }
 
-        // Tick to apply halt
+    /// Write `value` to register `idx` of the halted core
+    ///
+    /// Writes to x0 are dropped, it always reads as zero.
+    pub fn write_register(&self, idx: u8, value: u32) -> Result<()> {
+        self.check_register_access("write_register", idx)?;
+        if idx == 0 {
+            return Ok(());
+        }
+
+        self.log_debug(
+            self.now(),
+            DebugCommand::RegWrite {
+                reg: idx,
+                data: value,
+            },
+        );
+        let id_valid = self.model.borrow().get_debug_hart_in_id_valid();
+        self.start_register_request(true, value);
+        self.model
+            .borrow()
+            .set_debug_hart_in_bits_register_bits_reg(idx);
         self.tick(false);
+        self.model.borrow().set_debug_hart_in_bits_register_valid(0);
+        self.model
+            .borrow()
+            .set_debug_hart_in_bits_register_bits_write(0);
+        self.model.borrow().set_debug_hart_in_id_valid(id_valid);
 
-        // Read each register through debug interface
-        for idx in 0..32 {
-            self.model.borrow().set_debug_hart_in_id_valid(1);
-            self.model.borrow().set_debug_hart_in_id_bits(0); // Hart 0
-            self.model.borrow().set_debug_hart_in_bits_register_valid(1);
-            self.model
-                .borrow()
-                .set_debug_hart_in_bits_register_bits_reg(idx);
-            self.model
-                .borrow()
-                .set_debug_hart_in_bits_register_bits_write(0); // Read
-            self.model
-                .borrow()
-                .set_debug_hart_in_bits_register_bits_data(0);
+        Ok(())
+    }
 
-            // Tick to process request
-            self.tick(false);
+    /// Single register accesses need a valid index and a halted core, the
+    /// register file is only routed to the debug port while halted
+    fn check_register_access(&self, what: &str, idx: u8) -> Result<()> {
+        if idx >= 32 {
+            anyhow::bail!("{}: no register x{}", what, idx);
+        }
+        if self.model.borrow().get_debug_halted() == 0 {
+            anyhow::bail!("{} requires a halted core", what);
+        }
+        Ok(())
+    }
 
-            // Wait for result
-            let val = loop {
-                if self.model.borrow().get_debug_reg_res_valid() != 0 {
-                    break self.model.borrow().get_debug_reg_res_bits() as u32;
-                }
+    /// Address the selected hart and assert a register request
+    fn start_register_request(&self, write: bool, data: u32) {
+        self.model.borrow().set_debug_hart_in_id_valid(1);
+        self.model
+            .borrow()
+            .set_debug_hart_in_id_bits(*self.hart.borrow());
+        self.model.borrow().set_debug_hart_in_bits_register_valid(1);
+        self.model
+            .borrow()
+            .set_debug_hart_in_bits_register_bits_write(write as u8);
+        self.model
+            .borrow()
+            .set_debug_hart_in_bits_register_bits_data(data as u64);
+    }
 
-                self.tick(false);
-            };
+    /// Point the asserted read request at `idx` and collect the answer
+    fn request_register(&self, idx: u8) -> Result<u32> {
+        self.model
+            .borrow()
+            .set_debug_hart_in_bits_register_bits_reg(idx);
+        self.tick(false);
 
-            regs.set(idx, val);
+        // The result stays valid until the next request, so a valid flag
+        // right after the tick is always this request's answer
+        let time = self.now();
+        let model = self.model.borrow();
+        let value =
+            (model.get_debug_reg_res_valid() != 0).then(|| model.get_debug_reg_res_bits() as u32);
+        self.log_debug(time, DebugCommand::RegRead { reg: idx, value });
+        value.ok_or_else(|| {
+            anyhow::anyhow!(
+                "No valid debug response reading x{} from hart {}",
+                idx,
+                *self.hart.borrow()
+            )
+        })
+    }
+
+    /// Read all 32 registers through the debug interface
+    ///
+    /// Fails if a read is not answered with a valid response, instead of
+    /// recording whatever the response bits happen to hold.
+    fn read_register_file(&self) -> Result<RegisterFile> {
+        let mut regs = RegisterFile::new();
 
-            // Clear register request
-            self.model.borrow().set_debug_hart_in_bits_register_valid(0);
+        // Keep the read request asserted for the whole batch and only step the
+        // index. The hart registers the read data on the request edge, so each
+        // register takes a single tick and back-to-back requests need no idle
+        // cycles in between.
+        self.start_register_request(false, 0);
+
+        for idx in 0..32 {
+            match self.request_register(idx) {
+                Ok(value) => regs.set(idx, value),
+                Err(e) => {
+                    self.model.borrow().set_debug_hart_in_bits_register_valid(0);
+                    return Err(e);
+                }
+            }
         }
 
+        // Clear register request
+        self.model.borrow().set_debug_hart_in_bits_register_valid(0);
+
         Ok(regs)
     }
 
-    fn write_mem_byte(&self, addr: u32, data: u8) {
-        self.drive_mem_request(addr, data as u32, 0, true);
+    fn write_mem_byte(&self, addr: u32, data: u8) -> Result<()> {
+        self.drive_mem_request(addr, data as u32, 0, true)
+    }
+
+    fn write_mem_word(&self, addr: u32, data: u32) -> Result<()> {
+        self.drive_mem_request(addr, data, 2, true)
     }
 
-    fn write_mem_word(&self, addr: u32, data: u32) {
-        self.drive_mem_request(addr, data, 2, true);
+    fn drive_mem_request(&self, addr: u32, data: u32, req_width: u8, write: bool) -> Result<()> {
+        let time = self.now();
+        let result = self.issue_mem_request(addr, data, req_width, write);
+        if write {
+            self.log_debug(
+                time,
+                DebugCommand::MemWrite {
+                    addr,
+                    width: req_width,
+                    data,
+                    completed: result.is_ok(),
+                },
+            );
+        }
+        result
     }
 
-    fn drive_mem_request(&self, addr: u32, data: u32, req_width: u8, write: bool) {
+    fn issue_mem_request(&self, addr: u32, data: u32, req_width: u8, write: bool) -> Result<()> {
         // Wait for ready and send request
-        loop {
+        let mut accepted = false;
+        for _ in 0..MEM_READY_TIMEOUT {
             self.model.borrow().set_debug_mem_in_bits_addr(addr as u64);
             self.model
                 .borrow()
@@ -579,6 +1652,7 @@ impl Simulator {
             let ready = self.model.borrow().get_debug_mem_in_ready() != 0;
             self.tick(false);
             if ready {
+                accepted = true;
                 break;
             }
         }
@@ -587,76 +1661,395 @@ impl Simulator {
         self.model.borrow().set_debug_mem_in_valid(0);
         self.model.borrow().set_debug_mem_in_bits_write(0);
 
+        if !accepted {
+            return Err(self.mem_timeout_error(
+                addr,
+                write,
+                &format!("request not accepted after {} cycles", MEM_READY_TIMEOUT),
+            ));
+        }
+
         // For writes, wait for response to complete before returning
         // For reads, the caller will wait for and consume the response
         if write {
             // Wait for response to arrive and memPending to clear
             // Check mem_in.ready to ensure memPending has cleared
-            for _ in 0..30 {
+            let mut completed = false;
+            for _ in 0..MEM_RESPONSE_TIMEOUT {
                 self.tick(false);
                 let ready = self.model.borrow().get_debug_mem_in_ready() != 0;
                 if ready {
+                    completed = true;
                     break;
                 }
             }
+
+            if !completed {
+                return Err(self.mem_timeout_error(
+                    addr,
+                    write,
+                    &format!("write not completed after {} cycles", MEM_RESPONSE_TIMEOUT),
+                ));
+            }
         }
+
+        Ok(())
     }
 
-    #[allow(dead_code)]
-    pub fn read_mem_word(&self, addr: u32) -> u32 {
-        self.drive_mem_request(addr, 0, 2, false);
+    /// Describe a stuck debug memory request along with the handshake state
+    fn mem_timeout_error(&self, addr: u32, write: bool, what: &str) -> anyhow::Error {
+        let model = self.model.borrow();
+        anyhow::anyhow!(
+            "Debug memory {} at 0x{:08x} timed out: {} \
+             (mem_in_ready={}, mem_res_valid={}, mem_res_bits=0x{:08x})",
+            if write { "write" } else { "read" },
+            addr,
+            what,
+            model.get_debug_mem_in_ready(),
+            model.get_debug_mem_res_valid(),
+            model.mask_to_u32(model.get_debug_mem_res_bits())
+        )
+    }
 
-        let mut attempts = 0;
-        loop {
-            let response = if self.model.borrow().get_debug_mem_res_valid() != 0 {
-                Some(self.model.borrow().get_debug_mem_res_bits() as u32)
-            } else {
-                None
-            };
+    /// Read one word, failing unless a valid response was seen
+    ///
+    /// The response is only sampled after the request has been clocked in,
+    /// so a valid flag left over from an earlier transaction is never mistaken
+    /// for this read's data.
+    pub fn read_mem_word(&self, addr: u32) -> Result<u32> {
+        let time = self.now();
+        let result = self.await_mem_read(addr);
+        self.log_debug(
+            time,
+            DebugCommand::MemRead {
+                addr,
+                width: 2,
+                value: result.as_ref().ok().copied(),
+            },
+        );
+        result
+    }
 
-            if let Some(val) = response {
-                return val;
-            }
+    fn await_mem_read(&self, addr: u32) -> Result<u32> {
+        self.drive_mem_request(addr, 0, 2, false)?;
 
+        for _ in 0..MEM_RESPONSE_TIMEOUT {
             self.tick(false);
-            attempts += 1;
-            if attempts > 20 {
-                panic!("read_mem_word timeout");
+            let model = self.model.borrow();
+            if model.get_debug_mem_res_valid() != 0 {
+                return Ok(model.get_debug_mem_res_bits() as u32);
             }
         }
+
+        Err(self.mem_timeout_error(
+            addr,
+            false,
+            &format!("no valid response after {} cycles", MEM_RESPONSE_TIMEOUT),
+        ))
+    }
+
+    /// Read `len` bytes starting at `addr`
+    ///
+    /// Uses aligned word reads and drops the bytes outside the range, so the
+    /// start and length need no alignment.
+    pub fn read_mem(&self, addr: u32, len: usize) -> Result<Vec<u8>> {
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+
+        let start = addr & !3;
+        let end = addr as u64 + len as u64;
+
+        let mut bytes = Vec::with_capacity(len + 8);
+        let mut word_addr = start as u64;
+        while word_addr < end {
+            bytes.extend(self.read_mem_word(word_addr as u32)?.to_le_bytes());
+            word_addr += 4;
+        }
+
+        let offset = (addr - start) as usize;
+        bytes.truncate(offset + len);
+        Ok(bytes.split_off(offset))
+    }
+
+    /// Advance to the falling edge of the main clock
+    ///
+    /// Together with [`Self::tick_high`] this splits a clock cycle so inputs
+    /// can be changed between the edges. Both dump to the VCD while one is
+    /// open.
+    pub fn tick_low(&self) {
+        self.half_tick(true, 0);
+    }
+
+    /// Advance to the rising edge of the main clock, see [`Self::tick_low`]
+    pub fn tick_high(&self) {
+        self.half_tick(true, 1);
+    }
+
+    /// Start dumping the model's trace to `raw`, in whichever format it traces
+    fn open_trace(&self, raw: &Path) {
+        let model = self.model.borrow();
+        if model.has_fst() {
+            model.open_fst(raw.to_str().unwrap());
+        } else {
+            model.open_vcd(raw.to_str().unwrap());
+        }
+        *self.vcd_open.borrow_mut() = true;
+    }
+
+    fn close_trace(&self) {
+        let model = self.model.borrow();
+        if model.has_fst() {
+            model.close_fst();
+        } else {
+            model.close_vcd();
+        }
+        *self.vcd_open.borrow_mut() = false;
+    }
+
+    /// Current simulation time step
+    fn now(&self) -> u64 {
+        *self.timestamp.borrow()
+    }
+
+    /// Record a debug port transaction if logging is enabled
+    fn log_debug(&self, time: u64, command: DebugCommand) {
+        self.log_hart_debug(*self.hart.borrow(), time, command);
+    }
+
+    /// Record a debug port transaction addressed to `hart`
+    fn log_hart_debug(&self, hart: u8, time: u64, command: DebugCommand) {
+        if let Some(log) = &mut *self.debug_log.borrow_mut() {
+            log.record(DebugTxn {
+                time,
+                hart,
+                command,
+            });
+        }
+    }
+
+    /// Count an instruction retired by the selected hart in the last cycle
+    fn count_retired(&self) {
+        if self.model.borrow().get_debug_retired_valid() != 0 {
+            *self.instructions_retired.borrow_mut() += 1;
+        }
     }
 
     fn tick(&self, dump_vcd: bool) {
-        // Update RTC clock - runs at 1/50th of main clock frequency
-        let mut rtc_counter = self.rtc_counter.borrow_mut();
-        *rtc_counter += 1;
-        if *rtc_counter >= RTC_CLOCK_DIVIDER {
-            *rtc_counter = 0;
-            // Toggle RTC clock
-            let rtc_clk = self.model.borrow().get_rtc_clock();
-            self.model
-                .borrow()
-                .set_rtc_clock(if rtc_clk == 0 { 1 } else { 0 });
+        // One main clock period: falling edge, then rising edge
+        self.half_tick(dump_vcd, 0);
+        self.half_tick(dump_vcd, 1);
+    }
+
+    /// Put the next RX bit of every UART with queued input on its line
+    fn drive_uart_inputs(&self) {
+        let mut inputs = self.uart_inputs.borrow_mut();
+        let model = self.model.borrow();
+        if let Some(input) = &mut inputs[0] {
+            model.set_uart_0_rxd(input.next_level());
+        }
+        if let Some(input) = &mut inputs[1] {
+            model.set_uart_1_rxd(input.next_level());
         }
-        drop(rtc_counter);
+    }
 
-        self.model.borrow().set_clock(0);
-        self.model.borrow().eval();
-        if dump_vcd && *self.vcd_open.borrow() {
-            self.model.borrow().dump_vcd(*self.timestamp.borrow());
+    /// Step until the main clock is at `level`, taking one step when the
+    /// phases are aligned and two when the clock already was at `level`
+    ///
+    /// UART RX lines move on to their next bit ahead of every falling edge, so
+    /// [`Self::tick_low`] drives them just like a full tick does.
+    fn half_tick(&self, dump_vcd: bool, level: u8) {
+        if level == 0 {
+            self.drive_uart_inputs();
         }
-        *self.timestamp.borrow_mut() += 1;
+        for _ in 0..2 {
+            self.advance_clocks(dump_vcd);
+            if self.model.borrow().get_clock() == level {
+                break;
+            }
+        }
+    }
 
-        self.model.borrow().set_clock(1);
-        self.model.borrow().eval();
+    /// Advance every clock domain by half a main clock period and evaluate
+    fn advance_clocks(&self, dump_vcd: bool) {
+        let model = self.model.borrow();
+        for domain in self.clock_domains.borrow_mut().iter_mut() {
+            domain.advance(&*model);
+        }
 
+        model.eval();
         if dump_vcd && *self.vcd_open.borrow() {
-            self.model.borrow().dump_vcd(*self.timestamp.borrow());
+            if model.has_fst() {
+                model.dump_fst(*self.timestamp.borrow());
+            } else {
+                model.dump_vcd(*self.timestamp.borrow());
+            }
         }
         *self.timestamp.borrow_mut() += 1;
     }
 }
 
+/// Clocks driven by every model: the main clock and the RTC clock
+fn default_clock_domains() -> Vec<ClockDomain> {
+    vec![
+        // Main clock, starts high so the first step of a tick is the falling edge
+        ClockDomain::new(1, 1, |model, value| model.set_clock(value)),
+        // RTC clock toggles every DEFAULT_RTC_DIVIDER main clock cycles
+        ClockDomain::new(2 * DEFAULT_RTC_DIVIDER, 0, |model, value| {
+            model.set_rtc_clock(value)
+        }),
+    ]
+}
+
+/// Allocatable ELF section together with its contents
+struct ElfSection<'data> {
+    placement: SectionPlacement,
+    executable: bool,
+    /// `None` for sections without file contents (`SHT_NOBITS`)
+    data: Option<&'data [u8]>,
+}
+
+/// Check that `sections` fit in `regions`, that no writable section lands in
+/// a readonly region and, when any region is executable, that `entry_point`
+/// is in one of them
+fn check_placement(
+    regions: &[MemoryRegion],
+    sections: &[SectionPlacement],
+    entry_point: u32,
+) -> Result<()> {
+    let misplaced: Vec<String> = sections
+        .iter()
+        .filter(|section| !regions.iter().any(|region| region.contains(section)))
+        .map(|section| {
+            format!(
+                "{} (0x{:08x}..0x{:08x})",
+                section.name,
+                section.addr,
+                section.addr as u64 + section.size
+            )
+        })
+        .collect();
+    if !misplaced.is_empty() {
+        anyhow::bail!(
+            "Sections do not fit in any memory region: {}",
+            misplaced.join(", ")
+        );
+    }
+
+    let write_protected: Vec<&str> = sections
+        .iter()
+        .filter(|section| {
+            section.writable
+                && regions
+                    .iter()
+                    .any(|region| region.readonly && region.contains(section))
+        })
+        .map(|section| section.name.as_str())
+        .collect();
+    if !write_protected.is_empty() {
+        anyhow::bail!(
+            "Writable sections placed in readonly memory: {}",
+            write_protected.join(", ")
+        );
+    }
+
+    check_entry_point(regions, entry_point)
+}
+
+/// Check that `entry_point` is executable, when the config marks any region so
+fn check_entry_point(regions: &[MemoryRegion], entry_point: u32) -> Result<()> {
+    if regions.iter().any(|region| region.executable)
+        && !regions
+            .iter()
+            .any(|region| region.executable && region.contains_addr(entry_point))
+    {
+        anyhow::bail!(
+            "Entry point 0x{:08x} is not in an executable memory region",
+            entry_point
+        );
+    }
+
+    Ok(())
+}
+
+/// Collect the non-empty allocatable sections of an ELF file, in header order
+fn loadable_sections<'data>(file: &ElfBytes<'data, AnyEndian>) -> Result<Vec<ElfSection<'data>>> {
+    let (shdrs_opt, strtab_opt) = file.section_headers_with_strtab()?;
+    let (Some(shdrs), Some(strtab)) = (shdrs_opt, strtab_opt) else {
+        eprintln!("Warning: No section headers found in ELF file");
+        return Ok(Vec::new());
+    };
+
+    let mut sections = Vec::new();
+    for shdr in shdrs.iter() {
+        let is_alloc = (shdr.sh_flags & (SHF_ALLOC as u64)) != 0;
+        let is_nobits = shdr.sh_type == SHT_NOBITS;
+        if !is_alloc || shdr.sh_size == 0 {
+            continue;
+        }
+
+        let data = if is_nobits {
+            None
+        } else {
+            Some(file.section_data(&shdr)?.0)
+        };
+
+        sections.push(ElfSection {
+            placement: SectionPlacement {
+                name: strtab
+                    .get(shdr.sh_name as usize)
+                    .unwrap_or("<unknown>")
+                    .to_string(),
+                addr: shdr.sh_addr as u32,
+                size: shdr.sh_size,
+                zero_fill: is_nobits,
+                writable: (shdr.sh_flags & (SHF_WRITE as u64)) != 0,
+            },
+            executable: (shdr.sh_flags & (SHF_EXECINSTR as u64)) != 0,
+            data,
+        });
+    }
+
+    Ok(sections)
+}
+
+/// Resolve symbol addresses from the ELF symbol table
+///
+/// Symbols that are not found are reported and skipped. The result keeps the
+/// order of `names`.
+fn resolve_symbols(file: &ElfBytes<AnyEndian>, names: &[&str]) -> Result<Vec<(String, u32)>> {
+    if names.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let Some((symbols, strtab)) = file.symbol_table()? else {
+        eprintln!("Warning: No symbol table found in ELF file");
+        return Ok(Vec::new());
+    };
+
+    let mut resolved = Vec::with_capacity(names.len());
+    for &symbol_name in names {
+        let found = symbols.iter().find(|symbol| {
+            strtab
+                .get(symbol.st_name as usize)
+                .is_ok_and(|name| name == symbol_name)
+        });
+
+        match found {
+            Some(symbol) => {
+                eprintln!(
+                    "Found symbol '{}' at address 0x{:08x}",
+                    symbol_name, symbol.st_value
+                );
+                resolved.push((symbol_name.to_string(), symbol.st_value as u32));
+            }
+            None => eprintln!("Warning: symbol '{}' not found in ELF file", symbol_name),
+        }
+    }
+
+    Ok(resolved)
+}
+
 fn create_model(backend: Backend, model_name: &str) -> Result<Rc<RefCell<dyn SimulatorImpl>>> {
     match backend {
         Backend::Verilator => crate::models::create_verilator(model_name)
@@ -665,3 +2058,78 @@ fn create_model(backend: Backend, model_name: &str) -> Result<Rc<RefCell<dyn Sim
             .ok_or_else(|| anyhow::anyhow!("Unknown Verilator model: {}", model_name)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROM: MemoryRegion = MemoryRegion {
+        base: 0x0000_0000,
+        length: 0x1000,
+        readonly: true,
+        executable: true,
+    };
+    const RAM: MemoryRegion = MemoryRegion {
+        base: 0x8000_0000,
+        length: 0x1_0000,
+        readonly: false,
+        executable: false,
+    };
+
+    fn section(name: &str, addr: u32, size: u64, writable: bool) -> SectionPlacement {
+        SectionPlacement {
+            name: name.to_string(),
+            addr,
+            size,
+            zero_fill: false,
+            writable,
+        }
+    }
+
+    #[test]
+    fn placement_accepts_rom_code_and_ram_data() {
+        let sections = [
+            section(".text", 0x0000_0100, 0x200, false),
+            section(".data", 0x8000_0000, 0x40, true),
+        ];
+        check_placement(&[ROM, RAM], &sections, 0x0000_0100).unwrap();
+    }
+
+    #[test]
+    fn placement_rejects_writable_section_in_readonly_region() {
+        let sections = [section(".data", 0x0000_0800, 0x40, true)];
+        let err = check_placement(&[ROM, RAM], &sections, 0x0000_0100).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Writable sections placed in readonly memory: .data"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn placement_rejects_entry_outside_executable_regions() {
+        let sections = [section(".text", 0x8000_0000, 0x100, false)];
+        let err = check_placement(&[ROM, RAM], &sections, 0x8000_0000).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Entry point 0x80000000 is not in an executable memory region"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn placement_ignores_entry_without_executable_regions() {
+        let sections = [section(".text", 0x8000_0000, 0x100, false)];
+        check_placement(&[RAM], &sections, 0x8000_0000).unwrap();
+    }
+
+    #[test]
+    fn placement_rejects_section_outside_all_regions() {
+        let sections = [section(".text", 0x8000_fff0, 0x20, false)];
+        let err = check_placement(&[RAM], &sections, 0x8000_fff0).unwrap_err();
+        assert!(
+            err.to_string().contains(".text (0x8000fff0..0x80010010)"),
+            "{err}"
+        );
+    }
+}
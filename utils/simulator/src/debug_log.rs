@@ -0,0 +1,174 @@
+use std::fmt;
+use std::io::Write;
+
+/// Command issued through the chip debug port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugCommand {
+    /// Assert halt
+    Halt,
+    /// Release halt
+    Resume,
+    /// Redirect the halted core and flush its pipeline
+    SetPc { pc: u32 },
+    /// Arm the breakpoint
+    Breakpoint { pc: u32 },
+    /// Arm the store watchpoint
+    Watchpoint { addr: u32 },
+    /// Drive external interrupt line `irq` to `level`
+    Interrupt { irq: u32, level: bool },
+    /// Register read, `value` is `None` when no valid response came back
+    RegRead { reg: u8, value: Option<u32> },
+    /// Register write
+    RegWrite { reg: u8, data: u32 },
+    /// Memory read of `1 << width` bytes, `value` is `None` on timeout
+    MemRead {
+        addr: u32,
+        width: u8,
+        value: Option<u32>,
+    },
+    /// Memory write of `1 << width` bytes
+    MemWrite {
+        addr: u32,
+        width: u8,
+        data: u32,
+        completed: bool,
+    },
+}
+
+/// One debug port transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugTxn {
+    /// Simulation time step the command was issued at, as used in the VCD
+    pub time: u64,
+    /// Hart the command was addressed to
+    pub hart: u8,
+    pub command: DebugCommand,
+}
+
+/// Where transactions go once logging is enabled
+pub(crate) enum DebugLog {
+    /// Kept until [`crate::Simulator::take_debug_log`]
+    Recorded(Vec<DebugTxn>),
+    /// Written to the sink as they happen, one line per transaction
+    Streamed(Box<dyn Write>),
+}
+
+impl DebugLog {
+    pub(crate) fn record(&mut self, txn: DebugTxn) {
+        match self {
+            DebugLog::Recorded(txns) => txns.push(txn),
+            DebugLog::Streamed(sink) => {
+                writeln!(sink, "{}", txn).ok();
+            }
+        }
+    }
+
+    /// Recorded transactions, always empty when streaming
+    pub(crate) fn take(&mut self) -> Vec<DebugTxn> {
+        match self {
+            DebugLog::Recorded(txns) => std::mem::take(txns),
+            DebugLog::Streamed(_) => Vec::new(),
+        }
+    }
+
+    pub(crate) fn flush(&mut self) {
+        if let DebugLog::Streamed(sink) = self {
+            sink.flush().ok();
+        }
+    }
+}
+
+impl fmt::Display for DebugTxn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{:>10}] hart {}: ", self.time, self.hart)?;
+        match self.command {
+            DebugCommand::Halt => write!(f, "halt"),
+            DebugCommand::Resume => write!(f, "resume"),
+            DebugCommand::SetPc { pc } => write!(f, "set pc 0x{:08x}", pc),
+            DebugCommand::Breakpoint { pc } => write!(f, "breakpoint 0x{:08x}", pc),
+            DebugCommand::Watchpoint { addr } => write!(f, "watchpoint 0x{:08x}", addr),
+            DebugCommand::Interrupt { irq, level } => {
+                write!(
+                    f,
+                    "irq {} {}",
+                    irq,
+                    if level { "raised" } else { "lowered" }
+                )
+            }
+            DebugCommand::RegRead { reg, value } => match value {
+                Some(value) => write!(f, "read x{} = 0x{:08x}", reg, value),
+                None => write!(f, "read x{} (no response)", reg),
+            },
+            DebugCommand::RegWrite { reg, data } => write!(f, "write x{} = 0x{:08x}", reg, data),
+            DebugCommand::MemRead { addr, width, value } => match value {
+                Some(value) => write!(f, "mem read{} 0x{:08x} = 0x{:08x}", 8 << width, addr, value),
+                None => write!(f, "mem read{} 0x{:08x} (timed out)", 8 << width, addr),
+            },
+            DebugCommand::MemWrite {
+                addr,
+                width,
+                data,
+                completed,
+            } => write!(
+                f,
+                "mem write{} 0x{:08x} = 0x{:08x}{}",
+                8 << width,
+                addr,
+                data,
+                if completed { "" } else { " (timed out)" }
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    const HALT: DebugTxn = DebugTxn {
+        time: 42,
+        hart: 1,
+        command: DebugCommand::Halt,
+    };
+
+    #[test]
+    fn recorded_log_is_taken_once() {
+        let mut log = DebugLog::Recorded(Vec::new());
+        log.record(HALT);
+        assert_eq!(log.take(), vec![HALT]);
+        assert!(log.take().is_empty());
+    }
+
+    #[test]
+    fn streamed_log_writes_lines() {
+        let buf = SharedBuf::default();
+        let mut log = DebugLog::Streamed(Box::new(buf.clone()));
+        log.record(HALT);
+        log.record(DebugTxn {
+            command: DebugCommand::SetPc { pc: 0x8000_0000 },
+            ..HALT
+        });
+
+        assert!(log.take().is_empty());
+        assert_eq!(
+            String::from_utf8(buf.0.borrow().clone()).unwrap(),
+            "[        42] hart 1: halt\n[        42] hart 1: set pc 0x80000000\n"
+        );
+    }
+}
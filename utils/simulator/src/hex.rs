@@ -0,0 +1,228 @@
+use anyhow::{Context, Result, bail};
+
+/// Text memory image formats accepted by [`crate::Simulator::load_hex`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HexFormat {
+    /// `$readmemh` style: whitespace separated hex words with `@addr` records
+    ///
+    /// The word size is taken from the number of digits in each data token and
+    /// `@addr` counts in those words, as `$readmemh` does. Byte-wide images
+    /// (`objcopy -O verilog`) therefore use plain byte addresses.
+    Verilog,
+    /// Intel HEX records (`:LLAAAATT...CC`), including extended segment and
+    /// linear address records
+    Intel,
+}
+
+/// Decoded memory image
+pub(crate) struct HexImage {
+    /// Contiguous runs of bytes as `(start_addr, data)`, in file order
+    pub segments: Vec<(u32, Vec<u8>)>,
+    /// Start address record, if the file has one
+    pub entry: Option<u32>,
+}
+
+impl HexImage {
+    fn new() -> Self {
+        Self {
+            segments: Vec::new(),
+            entry: None,
+        }
+    }
+
+    /// Append bytes at `addr`, extending the last segment when contiguous
+    fn push(&mut self, addr: u32, bytes: &[u8]) {
+        if let Some((start, data)) = self.segments.last_mut()
+            && start.wrapping_add(data.len() as u32) == addr
+        {
+            data.extend_from_slice(bytes);
+            return;
+        }
+        self.segments.push((addr, bytes.to_vec()));
+    }
+}
+
+pub(crate) fn parse(text: &str, format: HexFormat) -> Result<HexImage> {
+    match format {
+        HexFormat::Verilog => parse_verilog(text),
+        HexFormat::Intel => parse_intel(text),
+    }
+}
+
+fn parse_verilog(text: &str) -> Result<HexImage> {
+    let mut image = HexImage::new();
+    let mut word_addr: u32 = 0;
+    // Word size is fixed by the first data token, `@addr` needs it to scale
+    let mut word_bytes: Option<u32> = None;
+    let mut pending_addr: Option<u32> = None;
+
+    for (line_no, line) in text.lines().enumerate() {
+        let line = line.split("//").next().unwrap_or("");
+        for token in line.split_whitespace() {
+            let token = token.replace('_', "");
+            if let Some(addr) = token.strip_prefix('@') {
+                let addr = u32::from_str_radix(addr, 16)
+                    .with_context(|| format!("line {}: bad address '{}'", line_no + 1, token))?;
+                pending_addr = Some(addr);
+                continue;
+            }
+
+            let value = u32::from_str_radix(&token, 16)
+                .with_context(|| format!("line {}: bad data word '{}'", line_no + 1, token))?;
+            let width = token.len().div_ceil(2) as u32;
+            if width > 4 {
+                bail!(
+                    "line {}: data word '{}' is wider than 32 bits",
+                    line_no + 1,
+                    token
+                );
+            }
+            let word_bytes = *word_bytes.get_or_insert(width);
+            if width > word_bytes {
+                bail!(
+                    "line {}: data word '{}' is wider than the {}-byte words before it",
+                    line_no + 1,
+                    token,
+                    word_bytes
+                );
+            }
+
+            if let Some(addr) = pending_addr.take() {
+                word_addr = addr;
+            }
+            let bytes = value.to_le_bytes();
+            image.push(
+                word_addr.wrapping_mul(word_bytes),
+                &bytes[..word_bytes as usize],
+            );
+            word_addr = word_addr.wrapping_add(1);
+        }
+    }
+
+    Ok(image)
+}
+
+fn parse_intel(text: &str) -> Result<HexImage> {
+    let mut image = HexImage::new();
+    let mut base: u32 = 0;
+
+    for (line_no, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let Some(record) = line.strip_prefix(':') else {
+            bail!("line {}: record does not start with ':'", line_no + 1);
+        };
+        if !record.is_ascii() || record.len() % 2 != 0 {
+            bail!("line {}: malformed hex digits", line_no + 1);
+        }
+        let bytes = (0..record.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&record[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .with_context(|| format!("line {}: invalid hex digits", line_no + 1))?;
+
+        if bytes.len() < 5 || bytes.len() != 5 + bytes[0] as usize {
+            bail!("line {}: record length mismatch", line_no + 1);
+        }
+        if bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != 0 {
+            bail!("line {}: checksum mismatch", line_no + 1);
+        }
+
+        let offset = u16::from_be_bytes([bytes[1], bytes[2]]) as u32;
+        let data = &bytes[4..bytes.len() - 1];
+        match bytes[3] {
+            // Data
+            0x00 => image.push(base.wrapping_add(offset), data),
+            // End of file
+            0x01 => break,
+            // Extended segment address
+            0x02 if data.len() == 2 => {
+                base = (u16::from_be_bytes([data[0], data[1]]) as u32) << 4;
+            }
+            // Start segment address (CS:IP)
+            0x03 if data.len() == 4 => {
+                let cs = u16::from_be_bytes([data[0], data[1]]) as u32;
+                let ip = u16::from_be_bytes([data[2], data[3]]) as u32;
+                image.entry = Some((cs << 4).wrapping_add(ip));
+            }
+            // Extended linear address
+            0x04 if data.len() == 2 => {
+                base = (u16::from_be_bytes([data[0], data[1]]) as u32) << 16;
+            }
+            // Start linear address
+            0x05 if data.len() == 4 => {
+                image.entry = Some(u32::from_be_bytes([data[0], data[1], data[2], data[3]]));
+            }
+            kind => bail!(
+                "line {}: malformed record of type {:02x}",
+                line_no + 1,
+                kind
+            ),
+        }
+    }
+
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verilog_addr_counts_in_words() {
+        let image = parse(
+            "@4 deadbeef 00000013\n@10 0000abcd // comment",
+            HexFormat::Verilog,
+        )
+        .unwrap();
+        assert_eq!(
+            image.segments,
+            vec![
+                (0x10, vec![0xef, 0xbe, 0xad, 0xde, 0x13, 0x00, 0x00, 0x00]),
+                (0x40, vec![0xcd, 0xab, 0x00, 0x00]),
+            ]
+        );
+        assert_eq!(image.entry, None);
+    }
+
+    #[test]
+    fn verilog_byte_image_uses_byte_addresses() {
+        let image = parse("@80000001 13 05\n", HexFormat::Verilog).unwrap();
+        assert_eq!(image.segments, vec![(0x8000_0001, vec![0x13, 0x05])]);
+    }
+
+    #[test]
+    fn intel_extended_linear_and_segment_records() {
+        let text = ":0200000480007A\n\
+                    :0400100013051000C4\n\
+                    :020000021000EC\n\
+                    :02000400AABB95\n\
+                    :00000001FF\n";
+        let image = parse(text, HexFormat::Intel).unwrap();
+        assert_eq!(
+            image.segments,
+            vec![
+                (0x8000_0010, vec![0x13, 0x05, 0x10, 0x00]),
+                (0x0001_0004, vec![0xaa, 0xbb]),
+            ]
+        );
+    }
+
+    #[test]
+    fn intel_start_linear_address() {
+        let image = parse(":040000058000001067\n:00000001FF\n", HexFormat::Intel).unwrap();
+        assert!(image.segments.is_empty());
+        assert_eq!(image.entry, Some(0x8000_0010));
+    }
+
+    #[test]
+    fn intel_rejects_bad_checksum() {
+        let err = parse(":0400100013051000C5\n", HexFormat::Intel)
+            .err()
+            .expect("bad checksum accepted");
+        assert!(err.to_string().contains("checksum mismatch"), "{err}");
+    }
+}
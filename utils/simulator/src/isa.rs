@@ -0,0 +1,199 @@
+/// Optional ISA features a model implements, or a result depends on
+///
+/// Only the features that decide whether a test result is comparable are
+/// tracked; the base integer ISA is always assumed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IsaFeatures {
+    /// `mul`/`mulh*` (M or Zmmul)
+    pub mul: bool,
+    /// `div*`/`rem*` (M only)
+    pub div: bool,
+    /// CSR instructions (Zicsr)
+    pub zicsr: bool,
+}
+
+impl IsaFeatures {
+    /// Features of an ISA string such as `rv32im_zicsr` or `rv32i_zmmul`
+    pub fn from_isa(isa: &str) -> Self {
+        let isa = isa.to_ascii_lowercase();
+        let mut parts = isa.split('_');
+        let base = parts.next().unwrap_or("");
+        // Single letter extensions follow the `rv32i`/`rv64i` base
+        let letters = base.get(5..).unwrap_or("");
+        let has_m = letters.contains('m');
+        let extensions: Vec<&str> = parts.collect();
+
+        Self {
+            mul: has_m || extensions.contains(&"zmmul"),
+            div: has_m,
+            zicsr: extensions.contains(&"zicsr"),
+        }
+    }
+
+    /// Features needed to execute the 32-bit instruction `inst`
+    pub fn of_instruction(inst: u32) -> Self {
+        let opcode = inst & 0x7f;
+        let funct3 = (inst >> 12) & 0x7;
+        let funct7 = inst >> 25;
+        // OP and OP-32 with funct7 = 1 are the M extension
+        let muldiv = (opcode == 0x33 || opcode == 0x3b) && funct7 == 1;
+
+        Self {
+            mul: muldiv && funct3 < 4,
+            div: muldiv && funct3 >= 4,
+            // SYSTEM with a non-zero funct3 is a CSR access
+            zicsr: opcode == 0x73 && funct3 != 0,
+        }
+    }
+
+    /// Every feature set in either
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            mul: self.mul || other.mul,
+            div: self.div || other.div,
+            zicsr: self.zicsr || other.zicsr,
+        }
+    }
+
+    /// Whether all features in `needed` are available here
+    pub fn covers(&self, needed: &Self) -> bool {
+        (self.mul || !needed.mul) && (self.div || !needed.div) && (self.zicsr || !needed.zicsr)
+    }
+}
+
+/// Address of the instruction that runs after the 32-bit instruction `inst`
+/// at `pc`
+///
+/// `reg` reads the register file as it is before `inst` executes. `None` for
+/// `ecall`, `ebreak` and `mret`, whose target lives in a CSR. Instructions
+/// that trap unexpectedly (illegal, misaligned) are assumed to fall through.
+pub(crate) fn next_pc<E>(
+    inst: u32,
+    pc: u32,
+    mut reg: impl FnMut(u8) -> Result<u32, E>,
+) -> Result<Option<u32>, E> {
+    let opcode = inst & 0x7f;
+    let funct3 = (inst >> 12) & 0x7;
+    let rs1 = ((inst >> 15) & 0x1f) as u8;
+    let rs2 = ((inst >> 20) & 0x1f) as u8;
+
+    let next = match opcode {
+        // JAL
+        0x6f => {
+            let imm = ((inst & 0x8000_0000) as i32 >> 11) as u32
+                | (inst & 0x000f_f000)
+                | ((inst >> 9) & 0x800)
+                | ((inst >> 20) & 0x7fe);
+            pc.wrapping_add(imm)
+        }
+        // JALR
+        0x67 => {
+            let imm = (inst as i32 >> 20) as u32;
+            reg(rs1)?.wrapping_add(imm) & !1
+        }
+        // BRANCH
+        0x63 => {
+            let (a, b) = (reg(rs1)?, reg(rs2)?);
+            let taken = match funct3 {
+                0 => a == b,
+                1 => a != b,
+                4 => (a as i32) < (b as i32),
+                5 => (a as i32) >= (b as i32),
+                6 => a < b,
+                7 => a >= b,
+                _ => false,
+            };
+            if !taken {
+                return Ok(Some(pc.wrapping_add(4)));
+            }
+            let imm = ((inst & 0x8000_0000) as i32 >> 19) as u32
+                | ((inst << 4) & 0x800)
+                | ((inst >> 20) & 0x7e0)
+                | ((inst >> 7) & 0x1e);
+            pc.wrapping_add(imm)
+        }
+        // SYSTEM without funct3: everything but `wfi` changes privilege
+        0x73 if funct3 == 0 && inst != 0x1050_0073 => return Ok(None),
+        _ => pc.wrapping_add(4),
+    };
+
+    Ok(Some(next))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn regs(idx: u8) -> Result<u32, ()> {
+        Ok(match idx {
+            5 => 0x8000_0100,
+            6 => 0xffff_ffff,
+            _ => 0,
+        })
+    }
+
+    #[test]
+    fn next_pc_straight_line() {
+        // addi x1, x0, 1
+        assert_eq!(
+            next_pc(0x0010_0093, 0x8000_0000, regs),
+            Ok(Some(0x8000_0004))
+        );
+    }
+
+    #[test]
+    fn next_pc_jumps() {
+        // jal x0, -8
+        assert_eq!(
+            next_pc(0xff9f_f06f, 0x8000_0010, regs),
+            Ok(Some(0x8000_0008))
+        );
+        // jal x1, 2048
+        assert_eq!(
+            next_pc(0x0010_00ef, 0x8000_0000, regs),
+            Ok(Some(0x8000_0800))
+        );
+        // jalr x1, 3(x5), the low bit is dropped
+        assert_eq!(
+            next_pc(0x0032_80e7, 0x8000_0000, regs),
+            Ok(Some(0x8000_0102))
+        );
+    }
+
+    #[test]
+    fn next_pc_branches() {
+        // beq x5, x5, 16
+        assert_eq!(
+            next_pc(0x0052_8863, 0x8000_0000, regs),
+            Ok(Some(0x8000_0010))
+        );
+        // bne x5, x5, 16
+        assert_eq!(
+            next_pc(0x0052_9863, 0x8000_0000, regs),
+            Ok(Some(0x8000_0004))
+        );
+        // blt x6, x0, -4: -1 < 0 signed
+        assert_eq!(
+            next_pc(0xfe03_4ee3, 0x8000_0008, regs),
+            Ok(Some(0x8000_0004))
+        );
+        // bltu x6, x0, -4: 0xffffffff is not below 0 unsigned
+        assert_eq!(
+            next_pc(0xfe03_6ee3, 0x8000_0008, regs),
+            Ok(Some(0x8000_000c))
+        );
+    }
+
+    #[test]
+    fn next_pc_traps_are_unknown() {
+        // ecall, ebreak, mret
+        for inst in [0x0000_0073, 0x0010_0073, 0x3020_0073] {
+            assert_eq!(next_pc(inst, 0x8000_0000, regs), Ok(None));
+        }
+        // wfi
+        assert_eq!(
+            next_pc(0x1050_0073, 0x8000_0000, regs),
+            Ok(Some(0x8000_0004))
+        );
+    }
+}
@@ -1,11 +1,38 @@
+mod clock;
 mod core;
+mod debug_log;
+mod hex;
+mod isa;
 mod models;
 mod register_file;
+mod stepper;
 mod uart;
 
 // Re-export public API
-pub use core::{Backend, Simulator};
-pub use register_file::{RegisterFile, TestResult};
+pub use core::{
+    Backend, HaltReason, LoadReport, LoadedBinary, MemoryRegion, SectionPlacement, Simulator,
+    StallDetection, TraceFormat,
+};
+pub use debug_log::{DebugCommand, DebugTxn};
+pub use hex::HexFormat;
+pub use isa::IsaFeatures;
+pub use register_file::{RegisterFile, StopReason, TestResult};
+pub use stepper::{CycleOutcome, Stepper};
+
+/// Static description of a model, available without instantiating it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelInfo {
+    pub name: &'static str,
+    /// ISA string from the model's config, e.g. `rv32i_zmmul_zicsr`
+    pub isa: &'static str,
+}
+
+impl ModelInfo {
+    /// Optional ISA features the model implements
+    pub fn features(&self) -> IsaFeatures {
+        IsaFeatures::from_isa(self.isa)
+    }
+}
 
 impl Simulator {
     /// List all available models
@@ -14,4 +41,40 @@ impl Simulator {
             Backend::Verilator | Backend::VerilatorMonitored => crate::models::VERILATOR_MODELS,
         }
     }
+
+    /// List the available models `predicate` accepts
+    ///
+    /// e.g. `Simulator::available_models_with(backend, |model| model.features().mul)`
+    /// for the models that can run the M-extension suite.
+    pub fn available_models_with(
+        backend: Backend,
+        predicate: impl Fn(&ModelInfo) -> bool,
+    ) -> Vec<&'static str> {
+        Self::available_models(backend)
+            .iter()
+            .filter_map(|&name| {
+                let isa = match backend {
+                    Backend::Verilator | Backend::VerilatorMonitored => {
+                        crate::models::verilator_model_isa(name)?
+                    }
+                };
+                predicate(&ModelInfo { name, isa }).then_some(name)
+            })
+            .collect()
+    }
+
+    /// Memory regions of `model_name` without building the model
+    ///
+    /// Same as [`Simulator::memory_regions`] on a simulator of that model,
+    /// `None` for unknown models.
+    pub fn model_memory_regions(
+        backend: Backend,
+        model_name: &str,
+    ) -> Option<&'static [MemoryRegion]> {
+        match backend {
+            Backend::Verilator | Backend::VerilatorMonitored => {
+                crate::models::verilator_model_memory_regions(model_name)
+            }
+        }
+    }
 }
@@ -1,15 +1,17 @@
 use anyhow::{Context, Result};
 use camino::Utf8PathBuf;
 use clap::Parser;
-use simulator::{Backend, Simulator};
+use simulator::{Backend, HexFormat, Simulator};
 use std::io::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 #[derive(Parser)]
 #[command(name = "svarog-sim")]
 #[command(about = "Standalone Svarog SoC simulator")]
 #[command(version)]
 struct Args {
-    /// Path to ELF binary to execute
+    /// Path to ELF binary, raw .bin or hex image (.hex/.ihex/.vh/.mem) to execute
     #[arg(value_name = "BINARY")]
     binary: Option<Utf8PathBuf>,
 
@@ -21,7 +23,9 @@ struct Args {
     #[arg(short, long)]
     model: Option<String>,
 
-    /// VCD output file
+    /// Trace output file: VCD, gzip compressed VCD if it ends in .gz (needs
+    /// gzip), or FST if it ends in .fst. FST needs a model built with
+    /// SVAROG_TRACE_FST=1, VCD from such a model needs GTKWave's fst2vcd
     #[arg(long)]
     vcd: Option<Utf8PathBuf>,
 
@@ -29,22 +33,43 @@ struct Args {
     #[arg(long, default_value = "100000")]
     max_cycles: usize,
 
-    /// Watchpoint symbol (e.g., "tohost") for ELF binaries
+    /// Cycles to run after the core halts before reading registers
+    #[arg(long, default_value = "5")]
+    settle_cycles: usize,
+
+    /// Main clock cycles per RTC clock toggle (half an RTC period), 0 stops the RTC
+    #[arg(long, default_value = "50")]
+    rtc_divider: u64,
+
+    /// Start running without toggling reset again after loading
     #[arg(long)]
-    watchpoint: Option<String>,
+    no_reset: bool,
+
+    /// Watchpoint symbol (e.g., "tohost") for ELF binaries, may be repeated
+    #[arg(long)]
+    watchpoint: Vec<String>,
 
     /// Watchpoint address (e.g., 0x80001000) for raw binaries
     #[arg(long, value_parser = parse_hex)]
     watchpoint_addr: Option<u32>,
 
-    /// Load address for raw binary files (default: 0x80000000)
+    /// Load address for raw binary files (default: the model's reset vector)
     #[arg(long, value_parser = parse_hex)]
     load_addr: Option<u32>,
 
-    /// Entry point / PC for raw binary files (default: same as load address)
+    /// Entry point / PC for raw binary and hex files (default: same as load
+    /// address, or the hex file's start/first address)
     #[arg(long, value_parser = parse_hex)]
     entry_point: Option<u32>,
 
+    /// Write a log of all debug port transactions to this file
+    #[arg(long)]
+    debug_log: Option<Utf8PathBuf>,
+
+    /// Hart to load, run and inspect
+    #[arg(long, default_value = "0")]
+    hart: u8,
+
     /// Enable UART console output (0 or 1)
     #[arg(long)]
     uart_console: Option<usize>,
@@ -127,6 +152,27 @@ fn main() -> Result<()> {
 
     // Create simulator
     let sim = Simulator::new(backend, &model_name).context("Failed to create simulator")?;
+    println!("Model initialised in {:.2?}", sim.init_time());
+    sim.select_hart(args.hart);
+    sim.set_reset_before_run(!args.no_reset);
+    sim.set_settle_cycles(args.settle_cycles);
+    sim.set_rtc_divider(args.rtc_divider);
+    if let Some(path) = &args.debug_log {
+        let file = std::fs::File::create(path).context("Failed to create debug log")?;
+        sim.enable_debug_log(Some(Box::new(std::io::BufWriter::new(file))));
+    }
+
+    // First Ctrl-C stops the run cleanly so the VCD is closed and registers are
+    // still dumped, a second one exits immediately
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let handler_flag = interrupted.clone();
+    ctrlc::set_handler(move || {
+        if handler_flag.swap(true, Ordering::Relaxed) {
+            std::process::exit(130);
+        }
+    })
+    .context("Failed to install Ctrl-C handler")?;
+    sim.set_stop_flag(Some(interrupted));
 
     // Enable UART console if requested
     if let Some(uart_index) = args.uart_console {
@@ -135,10 +181,34 @@ fn main() -> Result<()> {
 
     // Detect file type and load appropriately
     let is_raw_binary = binary.extension().map(|ext| ext == "bin").unwrap_or(false);
+    let hex_format = match binary.extension() {
+        Some("ihex" | "ihx") => Some(HexFormat::Intel),
+        Some("vh" | "mem") => Some(HexFormat::Verilog),
+        // .hex is used for both, Intel HEX records always start with ':'
+        Some("hex") => {
+            let text = std::fs::read_to_string(&binary).context("Failed to read hex file")?;
+            if text.trim_start().starts_with(':') {
+                Some(HexFormat::Intel)
+            } else {
+                Some(HexFormat::Verilog)
+            }
+        }
+        _ => None,
+    };
+
+    let entry_point = if let Some(format) = hex_format {
+        println!("Loading {:?} hex image: {}", format, binary);
+
+        let detected = sim
+            .load_hex(&binary, format)
+            .context("Failed to load hex image")?;
+        let entry = args.entry_point.unwrap_or(detected);
 
-    let entry_point = if is_raw_binary {
+        println!("  Entry point:  0x{:08x}", entry);
+        entry
+    } else if is_raw_binary {
         // Raw binary file
-        let load_addr = args.load_addr.unwrap_or(0x80000000);
+        let load_addr = args.load_addr.unwrap_or_else(|| sim.reset_vector());
         println!("Loading raw binary: {}", binary);
         println!("  Load address: 0x{:08x}", load_addr);
 
@@ -151,9 +221,13 @@ fn main() -> Result<()> {
     } else {
         // ELF file
         println!("Loading ELF binary: {}", binary);
-        sim.load_binary(&binary, args.watchpoint.as_deref())
+        let watchpoint_symbols: Vec<&str> = args.watchpoint.iter().map(String::as_str).collect();
+        let loaded = sim
+            .load_binary(&binary, &watchpoint_symbols)
             .context("Failed to load ELF binary")?;
-        0x80000000 // Default entry point for ELF
+
+        println!("  Entry point:  0x{:08x}", loaded.entry_point);
+        loaded.entry_point
     };
 
     // Run simulation
@@ -182,6 +256,7 @@ fn main() -> Result<()> {
             },
         )
         .context("Simulation failed")?;
+    sim.finalize();
     if show_progress {
         if last_drawn_cycle != last_seen_cycle {
             draw_progress(last_seen_cycle, args.max_cycles);
@@ -190,6 +265,11 @@ fn main() -> Result<()> {
     }
 
     println!("\nSimulation complete!");
+    println!("Stop reason: {:?}", result.stop_reason);
+    println!("Instructions retired: {}", result.instructions_retired);
+    if let Some(bit_period) = sim.uart_console_bit_period() {
+        println!("UART bit period: {} cycles", bit_period);
+    }
 
     if let Some(exit_code) = result.exit_code {
         println!("Exit code: {}", exit_code);
@@ -1,5 +1,10 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
 /// Register file state
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RegisterFile {
     regs: [u32; 32],
 }
@@ -27,9 +32,82 @@ impl Default for RegisterFile {
     }
 }
 
+/// Why a test run ended, comparable between simulators
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StopReason {
+    /// The watchpoint (usually `tohost`) fired
+    Watchpoint,
+    /// The cycle or log budget ran out first
+    Timeout,
+    /// The core stopped making progress
+    Stalled,
+    /// The program exited on its own without reaching the watchpoint
+    Exited,
+    /// The run was stopped from outside, e.g. by Ctrl-C
+    Interrupted,
+}
+
 /// Test result containing register state
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct TestResult {
     pub regs: RegisterFile,
     pub exit_code: Option<u32>,
+    pub stop_reason: StopReason,
+    /// Instructions retired during the run, for IPC and benchmark numbers
+    pub instructions_retired: u64,
+}
+
+impl TestResult {
+    /// Whether any register other than x0 ended up non-zero
+    ///
+    /// A run where this is false most likely never completed a writeback.
+    pub fn has_register_activity(&self) -> bool {
+        (1..32).any(|i| self.regs.get(i) != 0)
+    }
+
+    /// Write the result to `path` as JSON
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).context("Failed to serialize test result")?;
+        std::fs::write(path, json).with_context(|| format!("Failed to write {:?}", path))
+    }
+
+    /// Read a result written by [`Self::save`]
+    pub fn load(path: &Path) -> Result<Self> {
+        let json =
+            std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+        serde_json::from_str(&json).with_context(|| format!("Failed to parse {:?}", path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_round_trip() {
+        let mut regs = RegisterFile::new();
+        regs.set(1, 0x8000_0010);
+        regs.set(3, 1);
+        regs.set(31, 0xffff_ffff);
+        let result = TestResult {
+            regs,
+            exit_code: Some(3),
+            stop_reason: StopReason::Stalled,
+            instructions_retired: 12345,
+        };
+
+        let path =
+            std::env::temp_dir().join(format!("svarog_test_result_{}.json", std::process::id()));
+        result.save(&path).unwrap();
+        let loaded = TestResult::load(&path);
+        std::fs::remove_file(&path).ok();
+        let loaded = loaded.unwrap();
+
+        for idx in 0..32 {
+            assert_eq!(loaded.regs.get(idx), result.regs.get(idx), "x{idx}");
+        }
+        assert_eq!(loaded.exit_code, Some(3));
+        assert_eq!(loaded.stop_reason, StopReason::Stalled);
+        assert_eq!(loaded.instructions_retired, 12345);
+    }
 }
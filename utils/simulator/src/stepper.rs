@@ -0,0 +1,91 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use crate::TestResult;
+use crate::core::{HaltReason, LoopState, Simulator};
+
+/// Result of a single [`Stepper::advance`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CycleOutcome {
+    /// The cycle ran and the core keeps going
+    Running,
+    /// The run is over, further calls do not tick the model
+    Stopped(HaltReason),
+}
+
+/// A run driven one cycle at a time by the caller
+///
+/// Created by [`Simulator::stepper`]. Between cycles the simulator can be used
+/// freely, e.g. to read signals or decide to stop. A stepper finished before
+/// the core stopped reports [`crate::StopReason::Interrupted`], unless it was
+/// given up on with [`Stepper::time_out`].
+pub struct Stepper<'a> {
+    sim: &'a Simulator,
+    vcd_path: Option<PathBuf>,
+    raw_vcd: Option<PathBuf>,
+    cycle: usize,
+    state: LoopState,
+    stopped: Option<HaltReason>,
+}
+
+impl<'a> Stepper<'a> {
+    pub(crate) fn new(
+        sim: &'a Simulator,
+        vcd_path: Option<PathBuf>,
+        raw_vcd: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            sim,
+            vcd_path,
+            raw_vcd,
+            cycle: 0,
+            state: LoopState::default(),
+            stopped: None,
+        }
+    }
+
+    /// Simulate one cycle
+    pub fn advance(&mut self) -> CycleOutcome {
+        if let Some(reason) = self.stopped {
+            return CycleOutcome::Stopped(reason);
+        }
+
+        let dump_vcd = self.vcd_path.is_some();
+        match self.sim.run_cycle(self.cycle, dump_vcd, &mut self.state) {
+            Some(reason) => {
+                self.stopped = Some(reason);
+                CycleOutcome::Stopped(reason)
+            }
+            None => {
+                self.cycle += 1;
+                CycleOutcome::Running
+            }
+        }
+    }
+
+    /// Cycles simulated so far
+    pub fn cycle(&self) -> usize {
+        self.cycle
+    }
+
+    /// Simulator being stepped
+    pub fn simulator(&self) -> &'a Simulator {
+        self.sim
+    }
+
+    /// Give up on a run that did not stop within the caller's cycle budget
+    ///
+    /// Further calls to [`Self::advance`] do not tick the model and
+    /// [`Self::finish`] reports [`crate::StopReason::Timeout`].
+    pub fn time_out(&mut self) {
+        self.stopped.get_or_insert(HaltReason::MaxCyclesReached);
+    }
+
+    /// End the run: close the VCD and capture registers like [`Simulator::run`]
+    pub fn finish(self) -> Result<TestResult> {
+        let reason = self.stopped.unwrap_or(HaltReason::Interrupted);
+        self.sim
+            .finish_run(self.vcd_path.as_deref(), self.raw_vcd.as_deref(), reason)
+    }
+}
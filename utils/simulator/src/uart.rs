@@ -1,21 +1,34 @@
+use std::collections::VecDeque;
+
 /// UART byte decoder using transition-based decoding
 ///
 /// Decodes UART serial transmissions from single-bit TX line.
 /// Protocol: 1 start bit (0), 8 data bits (LSB first), 1 stop bit (1)
 /// Idle state: TX line is high (1)
 ///
-/// This decoder automatically detects the bit period by measuring transitions
-/// and decodes bytes by counting how long the line stays at each level.
+/// The bit period is either known up front or detected from the first burst
+/// (see [`UartDecoder::auto`]); bytes are decoded by sampling each bit in the
+/// middle of its period.
 pub struct UartDecoder {
     prev_txd: u8,
     bit_samples: Vec<u8>,    // Sampled bit values
     cycles_since_start: u32, // Cycles since start bit detected
     in_byte: bool,           // Track if we're currently receiving a byte
     bit_period: u32,         // Bit period in cycles (~434)
+    detect: Option<PeriodDetector>,
+    decoded: VecDeque<u8>, // Bytes decoded but not yet returned
+}
+
+/// Run lengths of the first burst, used to estimate the bit period
+struct PeriodDetector {
+    started: bool,
+    level: u8,
+    run: u32,
+    runs: Vec<(u8, u32)>, // Completed (level, length) runs since the start bit
 }
 
 impl UartDecoder {
-    pub fn new() -> Self {
+    pub fn with_bit_period(bit_period: u32) -> Self {
         Self {
             prev_txd: 1, // Idle is high
             bit_samples: Vec::new(),
@@ -23,15 +36,54 @@ impl UartDecoder {
             in_byte: false,
             // UART advances when counter reaches divider value, so each serial bit
             // lasts (divider + 1) core cycles.
-            bit_period: 435,
+            bit_period,
+            detect: None,
+            decoded: VecDeque::new(),
+        }
+    }
+
+    /// Decoder that detects the bit period from the first burst
+    ///
+    /// The line is recorded from the first start bit until it idles; the
+    /// shortest level run seen is taken as one bit, then the recording is
+    /// decoded with that period, which stays locked for all following bytes.
+    /// This needs the burst to contain an isolated bit (true for most text,
+    /// e.g. any byte with bit 0 set); a burst of 0x00 bytes yields a multiple
+    /// of the real period.
+    pub fn auto() -> Self {
+        Self {
+            detect: Some(PeriodDetector {
+                started: false,
+                level: 1,
+                run: 0,
+                runs: Vec::new(),
+            }),
+            ..Self::with_bit_period(0)
         }
     }
 
+    /// Bit period in cycles, `None` while still detecting it
+    pub fn bit_period(&self) -> Option<u32> {
+        self.detect.is_none().then_some(self.bit_period)
+    }
+
     /// Process one clock cycle of UART TX signal
-    /// Returns Some(byte) when a complete byte has been received
+    ///
+    /// Returns Some(byte) when a complete byte has been received. A detected
+    /// burst is handed out one byte per call.
     pub fn process(&mut self, txd: u8) -> Option<u8> {
         let txd_bit = txd & 1;
 
+        if self.detect.is_some() {
+            self.detect_period(txd_bit);
+        } else if let Some(byte) = self.decode(txd_bit) {
+            self.decoded.push_back(byte);
+        }
+
+        self.decoded.pop_front()
+    }
+
+    fn decode(&mut self, txd_bit: u8) -> Option<u8> {
         // Detect start bit (falling edge from 1 to 0)
         if !self.in_byte && self.prev_txd == 1 && txd_bit == 0 {
             self.in_byte = true;
@@ -64,6 +116,7 @@ impl UartDecoder {
                 self.in_byte = false;
                 self.bit_samples.clear();
                 self.cycles_since_start = 0;
+                self.prev_txd = txd_bit;
                 return Some(byte);
             }
         }
@@ -72,6 +125,59 @@ impl UartDecoder {
         None
     }
 
+    /// Record the first burst; once the line has idled long enough, lock the
+    /// period and decode the recorded runs with it
+    fn detect_period(&mut self, txd_bit: u8) {
+        let Some(detect) = self.detect.as_mut() else {
+            return;
+        };
+
+        if !detect.started {
+            // Wait for the falling edge of the first start bit
+            if self.prev_txd == 1 && txd_bit == 0 {
+                detect.started = true;
+                detect.level = 0;
+                detect.run = 1;
+            }
+            self.prev_txd = txd_bit;
+            return;
+        }
+
+        if txd_bit == detect.level {
+            detect.run += 1;
+        } else {
+            detect.runs.push((detect.level, detect.run));
+            detect.level = txd_bit;
+            detect.run = 1;
+        }
+        self.prev_txd = txd_bit;
+
+        // High runs inside a frame are at most 9 bits long (8 data bits and
+        // the stop bit), so a high run of ten times the shortest run means the
+        // burst is over
+        let Some(shortest) = detect.runs.iter().map(|&(_, len)| len).min() else {
+            return;
+        };
+        if detect.level == 0 || detect.run < shortest * 10 {
+            return;
+        }
+
+        let mut runs = std::mem::take(&mut detect.runs);
+        runs.push((detect.level, detect.run));
+        self.detect = None;
+        self.bit_period = shortest;
+
+        // Replay the burst through the regular decoder
+        self.prev_txd = 1;
+        for (level, len) in runs {
+            for _ in 0..len {
+                if let Some(byte) = self.decode(level) {
+                    self.decoded.push_back(byte);
+                }
+            }
+        }
+    }
+
     fn decode_bits(&self) -> u8 {
         let mut byte = 0u8;
         for (i, &bit) in self.bit_samples.iter().enumerate() {
@@ -82,3 +188,120 @@ impl UartDecoder {
         byte
     }
 }
+
+/// UART byte encoder driving a single-bit RX line
+///
+/// Counterpart of [`UartDecoder`]: queued bytes are sent back to back as
+/// 1 start bit, 8 data bits (LSB first) and 1 stop bit, each bit held for
+/// `bit_period` cycles. The line idles high once the queue is empty.
+pub struct UartEncoder {
+    bit_period: u32,
+    queue: VecDeque<u8>,
+    frame: Option<Frame>,
+}
+
+/// Frame currently on the line
+struct Frame {
+    bits: u16,    // Start, data and stop bits, sent from bit 0 up
+    index: u8,    // Bit being sent
+    elapsed: u32, // Cycles the current bit has been held
+}
+
+impl UartEncoder {
+    pub fn with_bit_period(bit_period: u32) -> Self {
+        Self {
+            bit_period: bit_period.max(1),
+            queue: VecDeque::new(),
+            frame: None,
+        }
+    }
+
+    /// Append `bytes` to the transmit queue
+    pub fn queue(&mut self, bytes: &[u8]) {
+        self.queue.extend(bytes);
+    }
+
+    /// Whether every queued byte has been sent
+    pub fn is_idle(&self) -> bool {
+        self.frame.is_none() && self.queue.is_empty()
+    }
+
+    /// Line level for the next clock cycle
+    pub fn next_level(&mut self) -> u8 {
+        if self.frame.is_none() {
+            let byte = match self.queue.pop_front() {
+                Some(byte) => byte,
+                None => return 1, // Idle is high
+            };
+            self.frame = Some(Frame {
+                bits: (1 << 9) | ((byte as u16) << 1),
+                index: 0,
+                elapsed: 0,
+            });
+        }
+
+        let frame = self.frame.as_mut().unwrap();
+        let level = ((frame.bits >> frame.index) & 1) as u8;
+        frame.elapsed += 1;
+        if frame.elapsed == self.bit_period {
+            frame.elapsed = 0;
+            frame.index += 1;
+            if frame.index == 10 {
+                self.frame = None;
+            }
+        }
+        level
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Send `bytes` back to back, let the line idle and collect what decodes
+    fn loopback(decoder: &mut UartDecoder, bit_period: u32, bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = UartEncoder::with_bit_period(bit_period);
+        encoder.queue(bytes);
+
+        let mut received = Vec::new();
+        let mut idle = 0;
+        while idle < 20 * bit_period {
+            if encoder.is_idle() {
+                idle += 1;
+            }
+            received.extend(decoder.process(encoder.next_level()));
+        }
+        received
+    }
+
+    #[test]
+    fn decodes_known_bit_period() {
+        let mut decoder = UartDecoder::with_bit_period(8);
+        assert_eq!(loopback(&mut decoder, 8, b"Hi\n"), b"Hi\n");
+    }
+
+    #[test]
+    fn detects_period_from_back_to_back_burst() {
+        let mut decoder = UartDecoder::auto();
+        assert_eq!(loopback(&mut decoder, 8, b"Hi\n"), b"Hi\n");
+        assert_eq!(decoder.bit_period(), Some(8));
+
+        // The period stays locked for later bytes
+        assert_eq!(loopback(&mut decoder, 8, b"ok"), b"ok");
+    }
+
+    #[test]
+    fn encoder_frames_bytes() {
+        let mut encoder = UartEncoder::with_bit_period(2);
+        assert_eq!(encoder.next_level(), 1);
+
+        encoder.queue(&[0x01]);
+        let levels: Vec<u8> = (0..20).map(|_| encoder.next_level()).collect();
+        // Start bit, data bits LSB first, stop bit, two cycles each
+        let bits = [0, 1, 0, 0, 0, 0, 0, 0, 0, 1];
+        let expected: Vec<u8> = bits.iter().flat_map(|&bit| [bit, bit]).collect();
+        assert_eq!(levels, expected);
+        assert!(encoder.is_idle());
+        assert_eq!(encoder.next_level(), 1);
+    }
+}